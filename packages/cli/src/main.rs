@@ -0,0 +1,193 @@
+//! Command line interface to import broker exports.
+
+use std::{
+    error::Error,
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use delfin::{
+    data_sources::{
+        degiro, detect_source,
+        exante::{self, ImportOptions},
+        open_file, SourceKind,
+    },
+    sink::{JsonlSink, TransactionSink},
+    transaction::Transaction,
+};
+
+#[derive(Parser)]
+#[command(name = "delfin", about = "Imports transactions from broker exports")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Imports a broker's CSV export, writing its transactions to the
+    /// standard output as JSON lines.
+    Import {
+        file: PathBuf,
+        /// Broker the export comes from, detected by its header when `auto`.
+        #[arg(long, value_enum, default_value_t = Source::Auto)]
+        source: Source,
+        /// Checks the export and prints a summary to the standard error
+        /// instead, failing when rows are dropped or transactions are invalid.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Source {
+    Auto,
+    Exante,
+    Degiro,
+}
+
+/// Outcome of reading an export.
+struct Import {
+    records_count: usize,
+    /// Rows which couldn't be read as records.
+    dropped_rows: usize,
+    /// Records which couldn't be converted into operations.
+    rejected: Vec<String>,
+    transactions: Vec<Transaction>,
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Import {
+            file,
+            source,
+            dry_run,
+        } => import(&file, source, dry_run),
+    };
+
+    result.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+
+        ExitCode::FAILURE
+    })
+}
+
+fn import(
+    file: &Path,
+    source: Source,
+    dry_run: bool,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let source = match source {
+        Source::Auto => {
+            let detected = detect_source(file).ok_or("Unrecognized export format")?;
+
+            eprintln!(
+                "Detected {:?} export ({:.0}% of its columns)",
+                detected.kind,
+                detected.confidence * 100.0
+            );
+
+            detected.kind
+        }
+        Source::Exante => SourceKind::Exante,
+        Source::Degiro => SourceKind::Degiro,
+    };
+
+    let Import {
+        records_count,
+        dropped_rows,
+        rejected,
+        transactions,
+    } = match source {
+        SourceKind::Exante => read_exante(file)?,
+        SourceKind::Degiro => read_degiro(file)?,
+    };
+
+    if !dry_run {
+        let mut sink = JsonlSink::new(io::stdout().lock());
+
+        for tx in transactions {
+            sink.accept(tx)?;
+        }
+        sink.finish()?;
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let issues = transactions
+        .iter()
+        .flat_map(|tx| tx.validate())
+        .filter(|issue| !issue.is_informational())
+        .collect::<Vec<_>>();
+
+    eprintln!("Records: {}", records_count);
+    eprintln!("Dropped rows: {}", dropped_rows);
+    eprintln!("Rejected records: {}", rejected.len());
+    eprintln!("Transactions: {}", transactions.len());
+    eprintln!("Issues: {}", issues.len());
+
+    for record in &rejected {
+        eprintln!("  {}", record);
+    }
+
+    for issue in &issues {
+        eprintln!("  {}", issue);
+    }
+
+    if dropped_rows > 0 || !rejected.is_empty() || !issues.is_empty() {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn read_exante(file: &Path) -> Result<Import, Box<dyn Error>> {
+    let mut records = vec![];
+    let mut dropped_rows = 0;
+
+    for row in exante::read_csv_rows(open_file(file)?, None) {
+        match row {
+            Ok(record) => records.push(record),
+            Err(_) => dropped_rows += 1,
+        }
+    }
+
+    let (transactions, rejected) =
+        exante::group_records_reporting_rejected(&records, &ImportOptions::default());
+
+    Ok(Import {
+        records_count: records.len(),
+        dropped_rows,
+        rejected: rejected
+            .iter()
+            .map(|record| format!("{}: {}", record.uuid, record.error))
+            .collect(),
+        transactions,
+    })
+}
+
+fn read_degiro(file: &Path) -> Result<Import, Box<dyn Error>> {
+    let mut records = vec![];
+    let mut dropped_rows = 0;
+
+    for row in degiro::read_csv_rows(open_file(file)?) {
+        match row {
+            Ok(record) => records.push(record),
+            Err(_) => dropped_rows += 1,
+        }
+    }
+
+    let (transactions, rejected) = degiro::group_records_reporting_rejected(&records);
+
+    Ok(Import {
+        records_count: records.len(),
+        dropped_rows,
+        rejected: rejected
+            .into_iter()
+            .map(|rejected| format!("row {}: {}", rejected.row, rejected.error))
+            .collect(),
+        transactions,
+    })
+}
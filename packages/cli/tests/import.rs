@@ -0,0 +1,72 @@
+use std::process::Command;
+
+fn dry_run(file: &str) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_delfin"))
+        .args(["import", "--dry-run", file])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn dry_run_of_valid_export_succeeds() {
+    let output = dry_run("../core/input/exante/demo.csv");
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn dry_run_fails_on_corrupt_row() {
+    let output = dry_run("tests/fixtures/corrupt.csv");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(stderr.contains("Dropped rows: 1"), "{}", stderr);
+}
+
+#[test]
+fn dry_run_fails_on_corrupt_degiro_row() {
+    let output = dry_run("tests/fixtures/corrupt_degiro.csv");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("Detected Degiro export"), "{}", stderr);
+    assert!(stderr.contains("Dropped rows: 1"), "{}", stderr);
+}
+
+#[test]
+fn source_is_detected_by_the_header() {
+    let output = dry_run("../core/input/degiro/demo.csv");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Detected Degiro export"), "{}", stderr);
+    assert!(stderr.contains("Transactions: 3"), "{}", stderr);
+}
+
+#[test]
+fn gzipped_export_is_imported() {
+    let output = Command::new(env!("CARGO_BIN_EXE_delfin"))
+        .args(["import", "tests/fixtures/demo.csv.gz"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Detected Exante export"), "{}", stderr);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 6);
+
+    let output = dry_run("tests/fixtures/demo.csv.gz");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Dropped rows: 0"), "{}", stderr);
+}
+
+#[test]
+fn unrecognized_export_fails() {
+    let output = dry_run("../core/input/exante/demo.json");
+
+    assert!(!output.status.success());
+}
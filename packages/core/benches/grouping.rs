@@ -0,0 +1,61 @@
+//! Times grouping of Exante records into transactions, the hot path of large
+//! imports.
+//!
+//! `cargo bench --bench grouping` measures, while `cargo test --benches` runs
+//! each benchmark once as a smoke test.
+
+use std::fmt::Write;
+
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use delfin::data_sources::exante::{
+    group_records_into_transactions, read_csv_reader, RawRecord,
+};
+use fake::{Fake, Faker};
+use rust_decimal::Decimal;
+
+const HEADER: &str = "Transaction ID\tAccount ID\tSymbol ID\tISIN\tOperation type\tWhen\tSum\tAsset\tEUR equivalent\tComment\tUUID\tParent UUID\n";
+
+/// Generates `count` cash records, three per second on average, so that
+/// records share timestamps as legs of a trade do.
+fn records(count: usize) -> Vec<RawRecord> {
+    let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+    let mut csv = String::from(HEADER);
+
+    for i in 0 .. count {
+        let when = start + Duration::seconds((i / 3) as i64);
+        let cents = (-100_000 .. 100_000).fake::<i64>();
+
+        writeln!(
+            csv,
+            "{i}\tABC1234.001\tNone\tNone\tTRADE\t{}\t{}\tUSD\t0\tNone\t{}\tNone",
+            when.format("%Y-%m-%d %H:%M:%S"),
+            Decimal::new(cents, 2),
+            Faker.fake::<u64>(),
+        )
+        .unwrap();
+    }
+
+    read_csv_reader(csv.as_bytes()).collect()
+}
+
+fn grouping(c: &mut Criterion) {
+    let mut group = c.benchmark_group("group_records_into_transactions");
+    group.sample_size(10);
+
+    for count in [1_000, 100_000, 1_000_000] {
+        let records = records(count);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &records,
+            |b, records| b.iter(|| group_records_into_transactions(records)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, grouping);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! Times parsing of ISINs, as of a large security master file.
+//!
+//! `regex_per_call` is the parser as it was before the pattern stopped being
+//! compiled on every call, kept as a baseline.
+//!
+//! `cargo bench --bench isin` measures, while `cargo test --benches` runs
+//! each benchmark once as a smoke test.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use delfin::asset::ISIN;
+use fake::{Fake, Faker};
+use regex::Regex;
+
+const COUNT: usize = 100_000;
+
+/// Generates `COUNT` inputs, half of them valid ISINs (some with dashes),
+/// the rest random strings.
+fn inputs() -> Vec<String> {
+    (0 .. COUNT)
+        .map(|i| match i % 4 {
+            0 => format!("US{:010}", (0 .. 10_000_000_000_u64).fake::<u64>()),
+            1 => format!("NA-000K0VF05-{}", i % 10),
+            _ => Faker.fake::<String>(),
+        })
+        .collect()
+}
+
+fn parse_with_regex_per_call(s: &str) -> bool {
+    let regex = Regex::new(r"^[A-Z]{2}[0-9A-Z]{10}$").unwrap();
+
+    regex.is_match(&s.replace('-', ""))
+}
+
+fn parsing(c: &mut Criterion) {
+    let inputs = inputs();
+    let mut group = c.benchmark_group("isin");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(COUNT as u64));
+
+    group.bench_function("from_str", |b| {
+        b.iter(|| {
+            inputs
+                .iter()
+                .filter(|input| input.parse::<ISIN>().is_ok())
+                .count()
+        })
+    });
+    group.bench_function("regex_per_call", |b| {
+        b.iter(|| {
+            inputs
+                .iter()
+                .filter(|input| parse_with_regex_per_call(input))
+                .count()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parsing);
+criterion_main!(benches);
@@ -0,0 +1,1519 @@
+//! Matches acquisitions against disposals of the same asset to find
+//! realized gains, using the first-in, first-out (FIFO) method.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use crate::{
+    asset::{AssetClass, AssetId, FiatCurrency, Money},
+    operation::{InflowOperation, Operation, OperationKind},
+    price::{self, PriceProvider},
+    transaction::{Transaction, TransactionId},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TradeSide {
+    Acquisition,
+    Disposal,
+}
+
+/// A single acquisition or disposal of an asset, together with the cash
+/// paid (cost) or received (proceeds) for it.
+#[derive(Clone, Debug)]
+pub struct Trade {
+    pub asset: AssetId,
+    pub side: TradeSide,
+    pub quantity: Decimal,
+    pub value: Money,
+    pub executed_at: DateTime<Utc>,
+    /// Transaction the trade was made by, when created from one.
+    pub transaction_id: Option<TransactionId>,
+    /// Price of a unit of the trade's currency the data source converted it
+    /// at, see [`Operation::fx_rate`].
+    pub fx_rate: Option<Money>,
+}
+
+impl Trade {
+    /// Creates trades from a transaction which exchanges exactly one
+    /// non-fiat asset for a single fiat currency.
+    ///
+    /// Adjustments are not part of the trade.
+    ///
+    /// The fiat legs flowing in the opposite direction to the asset make the
+    /// trade value, and the fiat legs flowing in the same direction (i.e.
+    /// commissions paid on a sale) are deducted from it.
+    ///
+    /// In a dividend reinvestment (see [`Transaction::is_drip`]) the dividend
+    /// is income rather than part of the trade, so the acquisition costs the
+    /// cash paid for it, or the reinvested dividend when no cash is paid.
+    ///
+    /// A reward received alone (see [`InflowOperation::Reward`]) is an
+    /// acquisition at zero cost, its value at receipt being income. The cost
+    /// is in the currency the data source valued the reward in (see
+    /// [`Operation::fx_rate`]), and rewards it didn't value make no trade, see
+    /// [`Trade::try_from_transaction`] to have them reported.
+    pub fn from_transaction(tx: &Transaction) -> Option<Trade> {
+        Trade::try_from_transaction(tx).ok().flatten()
+    }
+
+    /// Same as [`Trade::from_transaction`], but fails on a reward the data
+    /// source didn't value, which would otherwise be left out of the lots.
+    pub fn try_from_transaction(
+        tx: &Transaction,
+    ) -> Result<Option<Trade>, AccountingError> {
+        let is_drip = tx.is_drip();
+        let is_dividend =
+            |op: &Operation| op.kind == OperationKind::Inflow(InflowOperation::Dividend);
+
+        let (asset_legs, mut fiat_legs): (Vec<&Operation>, Vec<&Operation>) = tx
+            .operations()
+            .iter()
+            .filter(|op| !matches!(op.kind, OperationKind::Adjustment(_)))
+            .partition(|op| op.asset.class() != AssetClass::Fiat);
+
+        // the dividend pays for the acquisition only when no cash is paid
+        let dividend_paid = is_drip && fiat_legs.iter().all(|op| is_dividend(op));
+
+        if is_drip && !dividend_paid {
+            fiat_legs.retain(|op| !is_dividend(op));
+        }
+
+        let [asset_leg] = asset_legs.as_slice() else {
+            return Ok(None);
+        };
+
+        if fiat_legs.is_empty()
+            && matches!(
+                asset_leg.kind,
+                OperationKind::Inflow(InflowOperation::Reward { .. })
+            )
+        {
+            let Some(fx_rate) = &asset_leg.fx_rate else {
+                return Err(AccountingError::UnvaluedReward {
+                    asset: asset_leg.asset.id().to_owned(),
+                    received_at: asset_leg.executed_at,
+                });
+            };
+
+            return Ok(Some(Trade {
+                asset: asset_leg.asset.id().to_owned(),
+                side: TradeSide::Acquisition,
+                quantity: asset_leg.value,
+                value: Money::new(Decimal::ZERO, fx_rate.currency.to_owned()),
+                executed_at: asset_leg.executed_at,
+                transaction_id: Some(tx.id()),
+                // the rate of the reward is the price of the asset
+                fx_rate: None,
+            }));
+        }
+
+        let Some(currency) = single_currency(&fiat_legs) else {
+            return Ok(None);
+        };
+
+        let side = match asset_leg.kind {
+            OperationKind::Inflow(_) => TradeSide::Acquisition,
+            OperationKind::Outflow(_) => TradeSide::Disposal,
+            OperationKind::Adjustment(_) => return Ok(None),
+        };
+
+        let value = if dividend_paid {
+            fiat_legs.iter().map(|op| op.value).sum()
+        } else {
+            fiat_legs
+                .iter()
+                .fold(Decimal::ZERO, |value, op| match (&side, &op.kind) {
+                    (TradeSide::Acquisition, OperationKind::Outflow(_))
+                    | (TradeSide::Disposal, OperationKind::Inflow(_)) => value + op.value,
+                    _ => value - op.value,
+                })
+        };
+
+        Ok(Some(Trade {
+            asset: asset_leg.asset.id().to_owned(),
+            side,
+            quantity: asset_leg.value,
+            value: Money::new(value.abs(), currency),
+            executed_at: asset_leg.executed_at,
+            transaction_id: Some(tx.id()),
+            fx_rate: fiat_legs.iter().find_map(|op| op.fx_rate.to_owned()),
+        }))
+    }
+}
+
+/// Conversion of one fiat currency into another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FxTrade {
+    pub from_currency: FiatCurrency,
+    pub from_amount: Decimal,
+    pub to_currency: FiatCurrency,
+    pub to_amount: Decimal,
+    /// Amount of the target currency received per unit of the source one.
+    pub implied_rate: Decimal,
+}
+
+impl FxTrade {
+    /// Creates an FX trade from a transaction whose operations (adjustments
+    /// aside) are all in two fiat currencies, one flowing out and the other
+    /// in on balance.
+    ///
+    /// Fees paid in either currency are netted into its amount.
+    pub fn from_transaction(tx: &Transaction) -> Option<FxTrade> {
+        let mut net_amounts: Vec<(FiatCurrency, Decimal)> = vec![];
+
+        for operation in tx
+            .operations()
+            .iter()
+            .filter(|op| !matches!(op.kind, OperationKind::Adjustment(_)))
+        {
+            let AssetId::Currency(currency) = operation.asset.id() else {
+                return None;
+            };
+
+            match net_amounts.iter_mut().find(|(c, _)| c == currency) {
+                Some((_, amount)) => *amount += operation.signed_value(),
+                None => net_amounts.push((currency.to_owned(), operation.signed_value())),
+            }
+        }
+
+        let [(currency_a, amount_a), (currency_b, amount_b)] = net_amounts.as_slice()
+        else {
+            return None;
+        };
+
+        let ((from_currency, from_amount), (to_currency, to_amount)) =
+            if amount_a.is_sign_negative() && amount_b.is_sign_positive() {
+                ((currency_a, -amount_a), (currency_b, *amount_b))
+            } else if amount_b.is_sign_negative() && amount_a.is_sign_positive() {
+                ((currency_b, -amount_b), (currency_a, *amount_a))
+            } else {
+                return None;
+            };
+
+        if from_amount.is_zero() || to_amount.is_zero() {
+            return None;
+        }
+
+        Some(FxTrade {
+            from_currency: from_currency.to_owned(),
+            from_amount,
+            to_currency: to_currency.to_owned(),
+            to_amount,
+            implied_rate: to_amount / from_amount,
+        })
+    }
+}
+
+fn single_currency(operations: &[&Operation]) -> Option<FiatCurrency> {
+    let mut currencies = operations.iter().map(|op| match op.asset.id() {
+        AssetId::Currency(currency) => Some(currency),
+        _ => None,
+    });
+
+    let first = currencies.next()??;
+
+    currencies
+        .all(|currency| currency == Some(first))
+        .then(|| first.to_owned())
+}
+
+/// A position opened by one trade and not yet closed by another.
+///
+/// Long lots have a positive `quantity` and carry the cost paid, short lots
+/// have a negative `quantity` and carry the proceeds received.
+#[derive(Clone, Debug)]
+pub struct OpenLot {
+    pub asset: AssetId,
+    pub quantity: Decimal,
+    pub value: Money,
+    pub opened_at: DateTime<Utc>,
+    /// Absolute quantity the lot was opened with, before partial closes.
+    pub original_quantity: Decimal,
+    /// Rate of the opening trade, see [`Trade::fx_rate`].
+    pub fx_rate: Option<Money>,
+}
+
+impl OpenLot {
+    pub fn is_short(&self) -> bool {
+        self.quantity.is_sign_negative()
+    }
+}
+
+/// Quantity of an asset closed by a trade, matched against the opening lots
+/// it consumed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RealizedLot {
+    pub asset: AssetId,
+    pub quantity: Decimal,
+    pub short: bool,
+    /// When the oldest of the lots consumed was opened.
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: DateTime<Utc>,
+    pub cost_basis: Money,
+    pub proceeds: Money,
+    /// Opening lots the realized quantity was matched against, oldest first,
+    /// to show how the gain was computed.
+    pub consumed_from: Vec<LotRef>,
+    /// Transaction of the closing trade, i.e. of the disposal of a long lot.
+    pub transaction_id: Option<TransactionId>,
+    /// Rate of the closing trade, see [`Trade::fx_rate`]. Rates of the
+    /// opening ones are kept with the lots in `consumed_from`.
+    pub closing_fx_rate: Option<Money>,
+}
+
+/// Part of an opening lot consumed by a closing trade.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LotRef {
+    pub opened_at: DateTime<Utc>,
+    pub original_quantity: Decimal,
+    pub consumed: Decimal,
+    /// Cost of the consumed quantity, or its proceeds for a short lot.
+    pub value: Decimal,
+    /// Rate of the opening trade, see [`Trade::fx_rate`].
+    pub fx_rate: Option<Money>,
+}
+
+impl RealizedLot {
+    /// Gain (or loss when negative) realized on the lot.
+    ///
+    /// For a short position that's the proceeds of the opening sale less the
+    /// cost of the covering purchase.
+    pub fn gain(&self) -> Decimal {
+        self.proceeds.amount - self.cost_basis.amount
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum AccountingError {
+    #[error("Disposal of {disposed} {asset:?} exceeds {held} held")]
+    DisposalExceedsHoldings {
+        asset: AssetId,
+        disposed: Decimal,
+        held: Decimal,
+    },
+
+    #[error("Cover of {covered} {asset:?} exceeds {shorted} shorted")]
+    CoverExceedsShort {
+        asset: AssetId,
+        covered: Decimal,
+        shorted: Decimal,
+    },
+
+    #[error("Trade in {trade} cannot close a lot in {lot}")]
+    CurrencyMismatch {
+        lot: FiatCurrency,
+        trade: FiatCurrency,
+    },
+
+    #[error(
+        "Reward of {asset:?} received at {received_at} has no value to open a lot at"
+    )]
+    UnvaluedReward {
+        asset: AssetId,
+        received_at: DateTime<Utc>,
+    },
+}
+
+/// Identifiers an asset was known by, i.e. after a ticker change or an ISIN
+/// reissue, mapped to the one it's accounted under.
+///
+/// # Example
+/// ```
+/// use delfin::{accounting::AssetAliasMap, asset::AssetId};
+///
+/// let old = AssetId::Security("US30303M1027".parse().unwrap());
+/// let new = AssetId::Security("US0378331005".parse().unwrap());
+///
+/// let mut aliases = AssetAliasMap::default();
+/// aliases.alias(old.to_owned(), new.to_owned());
+///
+/// assert_eq!(aliases.canonical(&old), new);
+/// assert_eq!(aliases.canonical(&new), new);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AssetAliasMap {
+    aliases: HashMap<AssetId, AssetId>,
+}
+
+impl AssetAliasMap {
+    /// Registers `alias` as another identifier of `canonical`. Aliases may be
+    /// chained, i.e. for an asset renamed twice.
+    pub fn alias(&mut self, alias: AssetId, canonical: AssetId) -> &mut Self {
+        self.aliases.insert(alias, canonical);
+
+        self
+    }
+
+    /// Identifier the asset is accounted under, following chained aliases.
+    pub fn canonical(&self, asset: &AssetId) -> AssetId {
+        let mut canonical = asset;
+
+        // bounded by the number of aliases, so that a cycle can't hang
+        for _ in 0 .. self.aliases.len() {
+            match self.aliases.get(canonical) {
+                Some(next) if next != asset => canonical = next,
+                _ => break,
+            }
+        }
+
+        canonical.to_owned()
+    }
+}
+
+/// Keeps open lots per asset and closes them in the order they were opened.
+///
+/// A disposal made while holding no long position opens a short lot, which
+/// a later acquisition covers. A single trade never flips a position from
+/// long to short (or the other way round), such trades are reported as
+/// inconsistent data.
+///
+/// Lots are kept under the canonical identifier of their asset, see
+/// [`AssetAliasMap`].
+#[derive(Debug, Default)]
+pub struct FifoMatcher {
+    open_lots: HashMap<AssetId, VecDeque<OpenLot>>,
+    aliases: AssetAliasMap,
+}
+
+impl FifoMatcher {
+    pub fn with_aliases(aliases: AssetAliasMap) -> Self {
+        Self {
+            aliases,
+            ..Default::default()
+        }
+    }
+
+    pub fn open_lots(&self, asset: &AssetId) -> impl Iterator<Item = &OpenLot> {
+        self.open_lots
+            .get(&self.aliases.canonical(asset))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Net open quantity of an asset, negative when the position is short.
+    pub fn position(&self, asset: &AssetId) -> Decimal {
+        self.open_lots(asset).map(|lot| lot.quantity).sum()
+    }
+
+    pub fn apply(&mut self, trade: &Trade) -> Result<Vec<RealizedLot>, AccountingError> {
+        let trade = &Trade {
+            asset: self.aliases.canonical(&trade.asset),
+            ..trade.to_owned()
+        };
+        let position = self.position(&trade.asset);
+
+        let closes_position = match trade.side {
+            TradeSide::Acquisition => position.is_sign_negative() && !position.is_zero(),
+            TradeSide::Disposal => position.is_sign_positive() && !position.is_zero(),
+        };
+
+        if !closes_position {
+            let quantity = match trade.side {
+                TradeSide::Acquisition => trade.quantity,
+                TradeSide::Disposal => -trade.quantity,
+            };
+
+            self.open_lots
+                .entry(trade.asset.to_owned())
+                .or_default()
+                .push_back(OpenLot {
+                    asset: trade.asset.to_owned(),
+                    quantity,
+                    value: trade.value.to_owned(),
+                    opened_at: trade.executed_at,
+                    original_quantity: trade.quantity,
+                    fx_rate: trade.fx_rate.to_owned(),
+                });
+
+            return Ok(vec![]);
+        }
+
+        if trade.quantity > position.abs() {
+            return Err(match trade.side {
+                TradeSide::Acquisition => AccountingError::CoverExceedsShort {
+                    asset: trade.asset.to_owned(),
+                    covered: trade.quantity,
+                    shorted: position.abs(),
+                },
+                TradeSide::Disposal => AccountingError::DisposalExceedsHoldings {
+                    asset: trade.asset.to_owned(),
+                    disposed: trade.quantity,
+                    held: position,
+                },
+            });
+        }
+
+        let lots = self.open_lots.entry(trade.asset.to_owned()).or_default();
+
+        // every lot the trade closes is checked before any is, so that a
+        // mismatch leaves them as they were; a lot which cost nothing (i.e. a
+        // reward) can be closed in any currency
+        let mut to_close = trade.quantity;
+
+        for lot in lots.iter() {
+            if to_close <= Decimal::ZERO {
+                break;
+            }
+
+            if lot.value.currency != trade.value.currency && !lot.value.amount.is_zero() {
+                return Err(AccountingError::CurrencyMismatch {
+                    lot: lot.value.currency.to_owned(),
+                    trade: trade.value.currency.to_owned(),
+                });
+            }
+
+            to_close -= lot.quantity.abs();
+        }
+
+        let short = position.is_sign_negative();
+        let mut consumed_from = vec![];
+        let mut remaining = trade.quantity;
+
+        while !remaining.is_zero() {
+            let Some(lot) = lots.front_mut() else {
+                break;
+            };
+
+            let lot_quantity = lot.quantity.abs();
+            let matched = remaining.min(lot_quantity);
+
+            let lot_value = if matched == lot_quantity {
+                lot.value.amount
+            } else {
+                lot.value.amount * matched / lot_quantity
+            };
+
+            consumed_from.push(LotRef {
+                opened_at: lot.opened_at,
+                original_quantity: lot.original_quantity,
+                consumed: matched,
+                value: lot_value,
+                fx_rate: lot.fx_rate.to_owned(),
+            });
+
+            remaining -= matched;
+
+            if matched == lot_quantity {
+                lots.pop_front();
+            } else {
+                lot.value.amount -= lot_value;
+
+                if lot.is_short() {
+                    lot.quantity += matched;
+                } else {
+                    lot.quantity -= matched;
+                }
+            }
+        }
+
+        let opening_value = consumed_from.iter().map(|lot| lot.value).sum();
+        let (cost_basis, proceeds) = if short {
+            (trade.value.amount, opening_value)
+        } else {
+            (opening_value, trade.value.amount)
+        };
+
+        Ok(vec![RealizedLot {
+            asset: trade.asset.to_owned(),
+            quantity: trade.quantity,
+            short,
+            opened_at: consumed_from[0].opened_at,
+            closed_at: trade.executed_at,
+            cost_basis: Money::new(cost_basis, trade.value.currency.to_owned()),
+            proceeds: Money::new(proceeds, trade.value.currency.to_owned()),
+            consumed_from,
+            transaction_id: trade.transaction_id,
+            closing_fx_rate: trade.fx_rate.to_owned(),
+        }])
+    }
+}
+
+/// Realizes gains for trades processed in chronological order.
+pub fn match_fifo(trades: &[Trade]) -> Result<Vec<RealizedLot>, AccountingError> {
+    match_fifo_with_aliases(trades, &AssetAliasMap::default())
+}
+
+/// Same as [`match_fifo`], but matches trades in aliases of an asset as
+/// trades in the asset itself.
+pub fn match_fifo_with_aliases(
+    trades: &[Trade],
+    aliases: &AssetAliasMap,
+) -> Result<Vec<RealizedLot>, AccountingError> {
+    let mut trades = trades.iter().collect::<Vec<_>>();
+    trades.sort_by_key(|trade| trade.executed_at);
+
+    let mut matcher = FifoMatcher::with_aliases(aliases.to_owned());
+    let mut realized = vec![];
+
+    for trade in trades {
+        realized.extend(matcher.apply(trade)?);
+    }
+
+    Ok(realized)
+}
+
+/// Trades left after offsetting those made on the same day, and the gains
+/// realized by doing so, see [`net_same_day`].
+#[derive(Clone, Debug, Default)]
+pub struct SameDayNetting {
+    /// Net acquisition or disposal of each asset per day, in order of
+    /// execution.
+    pub trades: Vec<Trade>,
+    /// Gains realized on the quantity both acquired and disposed of on a
+    /// day, in order of closing.
+    pub realized: Vec<RealizedLot>,
+}
+
+/// Totals of the trades of one side made on a day.
+struct DayTotal {
+    quantity: Decimal,
+    value: Decimal,
+    first_at: DateTime<Utc>,
+    last_at: DateTime<Utc>,
+    transaction_id: Option<TransactionId>,
+    fx_rate: Option<Money>,
+}
+
+impl DayTotal {
+    fn of(trades: &[&Trade]) -> Self {
+        Self {
+            quantity: trades.iter().map(|trade| trade.quantity).sum(),
+            value: trades.iter().map(|trade| trade.value.amount).sum(),
+            first_at: trades.iter().map(|trade| trade.executed_at).min().unwrap(),
+            last_at: trades.iter().map(|trade| trade.executed_at).max().unwrap(),
+            // a single trade keeps its reference
+            transaction_id: match trades {
+                [trade] => trade.transaction_id,
+                _ => None,
+            },
+            fx_rate: match trades {
+                [trade] => trade.fx_rate.to_owned(),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Offsets acquisitions against disposals of the same asset made on the same
+/// day in the `tz` time zone, before the remaining trades are matched by
+/// FIFO, as required by the "same-day rule" of some tax regimes.
+///
+/// The quantity both acquired and disposed of is realized at the average
+/// cost and proceeds of the day, and only the net quantity is left to be
+/// matched. Trades in different currencies aren't offset.
+pub fn net_same_day<Tz: TimeZone>(
+    acquisitions: &[Trade],
+    disposals: &[Trade],
+    tz: &Tz,
+) -> SameDayNetting {
+    type Day<'a> = (Vec<&'a Trade>, Vec<&'a Trade>);
+
+    let key = |trade: &Trade| {
+        (
+            trade.asset.to_owned(),
+            trade.executed_at.with_timezone(tz).date_naive(),
+            trade.value.currency.to_owned(),
+        )
+    };
+
+    let mut days: HashMap<(AssetId, NaiveDate, FiatCurrency), Day> = HashMap::new();
+
+    for trade in acquisitions {
+        days.entry(key(trade)).or_default().0.push(trade);
+    }
+
+    for trade in disposals {
+        days.entry(key(trade)).or_default().1.push(trade);
+    }
+
+    let mut netting = SameDayNetting::default();
+
+    for ((asset, _, currency), (acquired, disposed)) in days {
+        if acquired.is_empty() || disposed.is_empty() {
+            netting
+                .trades
+                .extend(acquired.into_iter().chain(disposed).cloned());
+            continue;
+        }
+
+        let acquired = DayTotal::of(&acquired);
+        let disposed = DayTotal::of(&disposed);
+        let matched = acquired.quantity.min(disposed.quantity);
+
+        let share = |total: &DayTotal| {
+            if matched == total.quantity {
+                total.value
+            } else {
+                total.value * matched / total.quantity
+            }
+        };
+        let cost_basis = share(&acquired);
+        let proceeds = share(&disposed);
+
+        let short = disposed.first_at < acquired.first_at;
+        let (opening, closing, opening_value) = if short {
+            (&disposed, &acquired, proceeds)
+        } else {
+            (&acquired, &disposed, cost_basis)
+        };
+
+        netting.realized.push(RealizedLot {
+            asset: asset.to_owned(),
+            quantity: matched,
+            short,
+            opened_at: opening.first_at,
+            closed_at: closing.last_at,
+            cost_basis: Money::new(cost_basis, currency.to_owned()),
+            proceeds: Money::new(proceeds, currency.to_owned()),
+            consumed_from: vec![LotRef {
+                opened_at: opening.first_at,
+                original_quantity: opening.quantity,
+                consumed: matched,
+                value: opening_value,
+                fx_rate: opening.fx_rate.to_owned(),
+            }],
+            transaction_id: closing.transaction_id,
+            closing_fx_rate: closing.fx_rate.to_owned(),
+        });
+
+        if acquired.quantity > matched {
+            netting.trades.push(Trade {
+                asset,
+                side: TradeSide::Acquisition,
+                quantity: acquired.quantity - matched,
+                value: Money::new(acquired.value - cost_basis, currency),
+                executed_at: acquired.first_at,
+                transaction_id: acquired.transaction_id,
+                fx_rate: acquired.fx_rate,
+            });
+        } else if disposed.quantity > matched {
+            netting.trades.push(Trade {
+                asset,
+                side: TradeSide::Disposal,
+                quantity: disposed.quantity - matched,
+                value: Money::new(disposed.value - proceeds, currency),
+                executed_at: disposed.last_at,
+                transaction_id: disposed.transaction_id,
+                fx_rate: disposed.fx_rate,
+            });
+        }
+    }
+
+    netting.trades.sort_by_key(|trade| trade.executed_at);
+    netting.realized.sort_by_key(|lot| lot.closed_at);
+
+    netting
+}
+
+/// Same as [`match_fifo`], but when `same_day_tz` is given, trades made on
+/// the same day in that time zone are netted first, see [`net_same_day`].
+pub fn match_fifo_with_same_day_rule<Tz: TimeZone>(
+    trades: &[Trade],
+    same_day_tz: Option<&Tz>,
+) -> Result<Vec<RealizedLot>, AccountingError> {
+    let Some(tz) = same_day_tz else {
+        return match_fifo(trades);
+    };
+
+    let (acquisitions, disposals): (Vec<Trade>, Vec<Trade>) = trades
+        .iter()
+        .cloned()
+        .partition(|trade| trade.side == TradeSide::Acquisition);
+
+    let netting = net_same_day(&acquisitions, &disposals, tz);
+    let mut realized = netting.realized;
+    realized.extend(match_fifo(&netting.trades)?);
+    realized.sort_by_key(|lot| lot.closed_at);
+
+    Ok(realized)
+}
+
+/// Method of choosing the open lots a disposal is matched against.
+///
+/// Only first-in, first-out is supported for now, see [`FifoMatcher`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+}
+
+/// Position opened and then fully closed, summed over all the lots realized
+/// while it was open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClosedPosition {
+    pub asset: AssetId,
+    pub quantity: Decimal,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: DateTime<Utc>,
+    pub cost_basis: Money,
+    pub proceeds: Money,
+}
+
+impl ClosedPosition {
+    /// Net gain (or loss when negative) realized on the position.
+    pub fn gain(&self) -> Decimal {
+        self.proceeds.amount - self.cost_basis.amount
+    }
+}
+
+/// Positions whose quantity reached zero within `range` (start inclusive,
+/// end exclusive).
+///
+/// Trades made before the range are matched too, so that a position opened
+/// earlier is reported with its full cost. Positions still open at the end
+/// of the range aren't reported.
+pub fn closed_positions(
+    txs: &[Transaction],
+    range: (DateTime<Utc>, DateTime<Utc>),
+    method: CostBasisMethod,
+) -> Result<Vec<ClosedPosition>, AccountingError> {
+    let (start, end) = range;
+
+    let mut trades = txs
+        .iter()
+        .filter_map(|tx| Trade::try_from_transaction(tx).transpose())
+        .filter(|trade| !matches!(trade, Ok(trade) if trade.executed_at >= end))
+        .collect::<Result<Vec<_>, _>>()?;
+    trades.sort_by_key(|trade| trade.executed_at);
+
+    let mut matcher = match method {
+        CostBasisMethod::Fifo => FifoMatcher::default(),
+    };
+    let mut realized_by_asset: HashMap<AssetId, Vec<RealizedLot>> = HashMap::new();
+    let mut closed = vec![];
+
+    for trade in &trades {
+        let realized = matcher.apply(trade)?;
+
+        if realized.is_empty() {
+            continue;
+        }
+
+        let lots = realized_by_asset.entry(trade.asset.to_owned()).or_default();
+        lots.extend(realized);
+
+        if !matcher.position(&trade.asset).is_zero() {
+            continue;
+        }
+
+        let lots = std::mem::take(lots);
+
+        if trade.executed_at < start {
+            continue;
+        }
+
+        let currency = trade.value.currency.to_owned();
+        let sum = |amount: fn(&RealizedLot) -> Decimal| lots.iter().map(amount).sum();
+
+        closed.push(ClosedPosition {
+            asset: trade.asset.to_owned(),
+            quantity: sum(|lot| lot.quantity),
+            opened_at: lots.iter().map(|lot| lot.opened_at).min().unwrap_or(start),
+            closed_at: trade.executed_at,
+            cost_basis: Money::new(sum(|lot| lot.cost_basis.amount), currency.to_owned()),
+            proceeds: Money::new(sum(|lot| lot.proceeds.amount), currency),
+        });
+    }
+
+    Ok(closed)
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConversionError {
+    #[error("Missing {from} to {to} rate on {date}")]
+    MissingRate {
+        from: FiatCurrency,
+        to: FiatCurrency,
+        date: NaiveDate,
+    },
+}
+
+/// Expresses realized lots in the `home` currency.
+///
+/// The cost basis is converted at the rates from the days the lots consumed
+/// were opened, each at its own, and the proceeds at the rate from the day
+/// the lot was closed (the other way round for short lots), as both happen
+/// on different days.
+///
+/// The rate captured from the data source (see [`Trade::fx_rate`]) is
+/// preferred over the provider's, as in [`Operation::value_in`].
+pub fn convert_lots_to_currency(
+    lots: &[RealizedLot],
+    home: FiatCurrency,
+    provider: &dyn PriceProvider,
+) -> Result<Vec<RealizedLot>, ConversionError> {
+    let convert = |money: &Money, at: DateTime<Utc>, fx_rate: &Option<Money>| {
+        if let Some(rate) = fx_rate.as_ref().filter(|rate| rate.currency == home) {
+            return Ok(Money::new(money.amount * rate.amount, home.to_owned()));
+        }
+
+        price::convert(money, &home, at, provider).ok_or_else(|| {
+            ConversionError::MissingRate {
+                from: money.currency.to_owned(),
+                to: home.to_owned(),
+                date: at.date_naive(),
+            }
+        })
+    };
+
+    lots.iter()
+        .map(|lot| {
+            let (opening, closing) = if lot.short {
+                (&lot.proceeds, &lot.cost_basis)
+            } else {
+                (&lot.cost_basis, &lot.proceeds)
+            };
+
+            let consumed_from = lot
+                .consumed_from
+                .iter()
+                .map(|part| {
+                    let value = Money::new(part.value, opening.currency.to_owned());
+
+                    Ok(LotRef {
+                        value: convert(&value, part.opened_at, &part.fx_rate)?.amount,
+                        ..part.to_owned()
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let opening = Money::new(
+                consumed_from.iter().map(|part| part.value).sum(),
+                home.to_owned(),
+            );
+            let closing = convert(closing, lot.closed_at, &lot.closing_fx_rate)?;
+
+            let (cost_basis, proceeds) = if lot.short {
+                (closing, opening)
+            } else {
+                (opening, closing)
+            };
+
+            Ok(RealizedLot {
+                cost_basis,
+                proceeds,
+                consumed_from,
+                ..lot.to_owned()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use claim::{assert_matches, assert_ok};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::{TokenId, ISIN},
+        operation::{
+            test::operation, FeeCategory, InflowOperation, OutflowOperation, RewardKind,
+        },
+        price::StaticPriceProvider,
+        tax::{classify_tax_category, TaxCategory},
+        transaction::TransactionBuilder,
+    };
+
+    fn aapl() -> AssetId {
+        AssetId::Security("US0378331005".parse::<ISIN>().unwrap())
+    }
+
+    fn trade(side: TradeSide, quantity: Decimal, value: Decimal, day: u32) -> Trade {
+        Trade {
+            asset: aapl(),
+            side,
+            quantity,
+            value: Money::new(value, FiatCurrency::USD),
+            executed_at: Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap(),
+            transaction_id: None,
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn reinvested_dividend_is_income_and_opens_a_lot() {
+        let tx = TransactionBuilder::default()
+            .add_operation(operation(
+                OperationKind::Inflow(InflowOperation::Dividend),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(10),
+            ))
+            .add_operation(operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                aapl(),
+                dec!(0.08),
+            ))
+            .build()
+            .unwrap();
+
+        assert!(tx.is_drip());
+        assert_eq!(classify_tax_category(&tx), TaxCategory::DividendIncome);
+
+        let mut matcher = FifoMatcher::default();
+        matcher
+            .apply(&Trade::from_transaction(&tx).unwrap())
+            .unwrap();
+
+        let lot = matcher.open_lots(&aapl()).next().unwrap();
+
+        assert_eq!(lot.quantity, dec!(0.08));
+        assert_eq!(lot.value, Money::new(dec!(10), FiatCurrency::USD));
+    }
+
+    #[test]
+    fn staking_reward_is_income_and_opens_a_zero_cost_lot() {
+        let eth = AssetId::Token(TokenId("ETH".into()));
+        let tx = Transaction::from_operations(vec![Operation {
+            fx_rate: Some(Money::new(dec!(1800), FiatCurrency::USD)),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Reward {
+                    kind: RewardKind::Staking,
+                }),
+                eth.to_owned(),
+                dec!(0.01),
+            )
+        }])
+        .unwrap();
+
+        assert_eq!(classify_tax_category(&tx), TaxCategory::OtherIncome);
+
+        let mut matcher = FifoMatcher::default();
+        matcher
+            .apply(&Trade::from_transaction(&tx).unwrap())
+            .unwrap();
+
+        let lot = matcher.open_lots(&eth).next().unwrap();
+
+        assert_eq!(lot.quantity, dec!(0.01));
+        assert_eq!(lot.value, Money::new(Decimal::ZERO, FiatCurrency::USD));
+    }
+
+    #[test]
+    fn every_lot_closed_is_checked_for_its_currency() {
+        let reward = trade(TradeSide::Acquisition, dec!(1), Decimal::ZERO, 1);
+        let purchase = trade(TradeSide::Acquisition, dec!(1), dec!(100), 2);
+        let in_eur = |quantity, value| Trade {
+            value: Money::new(value, FiatCurrency::EUR),
+            ..trade(TradeSide::Disposal, quantity, Decimal::ZERO, 3)
+        };
+        let mut matcher = FifoMatcher::default();
+
+        matcher.apply(&reward).unwrap();
+        matcher.apply(&purchase).unwrap();
+
+        assert_eq!(
+            matcher.apply(&in_eur(dec!(2), dec!(240))),
+            Err(AccountingError::CurrencyMismatch {
+                lot: FiatCurrency::USD,
+                trade: FiatCurrency::EUR,
+            })
+        );
+        assert_eq!(matcher.position(&aapl()), dec!(2));
+
+        let realized = matcher.apply(&in_eur(dec!(1), dec!(120))).unwrap();
+
+        assert_eq!(
+            realized[0].cost_basis,
+            Money::new(Decimal::ZERO, FiatCurrency::EUR)
+        );
+        assert_eq!(
+            realized[0].proceeds,
+            Money::new(dec!(120), FiatCurrency::EUR)
+        );
+    }
+
+    #[test]
+    fn reward_without_value_is_reported() {
+        let tx = Transaction::from_operations(vec![operation(
+            OperationKind::Inflow(InflowOperation::Reward {
+                kind: RewardKind::Airdrop,
+            }),
+            AssetId::Token(TokenId("ETH".into())),
+            dec!(0.01),
+        )])
+        .unwrap();
+        let range = (
+            Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+        );
+
+        assert_matches!(
+            Trade::try_from_transaction(&tx),
+            Err(AccountingError::UnvaluedReward { .. })
+        );
+        assert_matches!(
+            closed_positions(&[tx], range, CostBasisMethod::Fifo),
+            Err(AccountingError::UnvaluedReward { .. })
+        );
+    }
+
+    #[test]
+    fn trade_is_created_from_a_purchase_with_commission() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+
+        let tx = TransactionBuilder::default()
+            .add_operation(operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                aapl(),
+                dec!(10),
+            ))
+            .add_operation(operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                usd.to_owned(),
+                dec!(1300.5),
+            ))
+            .add_operation(operation(
+                OperationKind::Outflow(OutflowOperation::Fee {
+                    category: FeeCategory::Commission,
+                }),
+                usd,
+                dec!(2),
+            ))
+            .build()
+            .unwrap();
+
+        let trade = Trade::from_transaction(&tx).unwrap();
+
+        assert_eq!(trade.side, TradeSide::Acquisition);
+        assert_eq!(trade.quantity, dec!(10));
+        assert_eq!(trade.value, Money::new(dec!(1302.5), FiatCurrency::USD));
+    }
+
+    #[test]
+    fn usd_to_eur_conversion_is_an_fx_trade() {
+        let tx = TransactionBuilder::default()
+            .add_operation(operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(1200),
+            ))
+            .add_operation(operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::EUR),
+                dec!(1000),
+            ))
+            .build()
+            .unwrap();
+
+        let fx_trade = tx.as_fx_trade().unwrap();
+
+        assert_eq!(fx_trade.from_currency, FiatCurrency::USD);
+        assert_eq!(fx_trade.from_amount, dec!(1200));
+        assert_eq!(fx_trade.to_currency, FiatCurrency::EUR);
+        assert_eq!(fx_trade.to_amount, dec!(1000));
+        assert_eq!(fx_trade.implied_rate.round_dp(4), dec!(0.8333));
+    }
+
+    #[test]
+    fn disposal_closes_oldest_lot_first() {
+        let realized = match_fifo(&[
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Acquisition, dec!(10), dec!(1200), 2),
+            trade(TradeSide::Disposal, dec!(15), dec!(1950), 3),
+        ]);
+
+        let realized = assert_ok!(realized);
+
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].quantity, dec!(15));
+        assert_eq!(realized[0].cost_basis.amount, dec!(1600));
+        assert_eq!(realized[0].gain(), dec!(350));
+    }
+
+    #[test]
+    fn shares_sold_under_an_alias_close_lots_of_the_asset() {
+        let renamed = AssetId::Security("US30303M1027".parse::<ISIN>().unwrap());
+        let mut aliases = AssetAliasMap::default();
+        aliases.alias(renamed.to_owned(), aapl());
+
+        let trades = [
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            Trade {
+                asset: renamed,
+                ..trade(TradeSide::Disposal, dec!(10), dec!(1300), 2)
+            },
+        ];
+
+        // otherwise the sale opens a short position in another asset
+        assert_eq!(match_fifo(&trades), Ok(vec![]));
+
+        let realized = assert_ok!(match_fifo_with_aliases(&trades, &aliases));
+
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].asset, aapl());
+        assert_eq!(realized[0].gain(), dec!(300));
+    }
+
+    #[test]
+    fn disposal_spanning_two_lots_lists_both() {
+        let at = |day| Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap();
+
+        let realized = match_fifo(&[
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Acquisition, dec!(10), dec!(1200), 2),
+            trade(TradeSide::Disposal, dec!(15), dec!(1950), 3),
+        ])
+        .unwrap();
+
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].opened_at, at(1));
+        assert_eq!(realized[0].consumed_from, vec![
+            LotRef {
+                opened_at: at(1),
+                original_quantity: dec!(10),
+                consumed: dec!(10),
+                value: dec!(1000),
+                fx_rate: None,
+            },
+            LotRef {
+                opened_at: at(2),
+                original_quantity: dec!(10),
+                consumed: dec!(5),
+                value: dec!(600),
+                fx_rate: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn short_sale_is_realized_when_covered() {
+        let realized = match_fifo(&[
+            trade(TradeSide::Disposal, dec!(10), dec!(1500), 1),
+            trade(TradeSide::Acquisition, dec!(4), dec!(480), 2),
+            trade(TradeSide::Acquisition, dec!(6), dec!(720), 3),
+        ]);
+
+        let realized = assert_ok!(realized);
+
+        assert_eq!(realized.len(), 2);
+        assert!(realized.iter().all(|lot| lot.short));
+        assert_eq!(realized[0].proceeds.amount, dec!(600));
+        assert_eq!(realized[0].gain(), dec!(120));
+        assert_eq!(
+            realized.iter().map(RealizedLot::gain).sum::<Decimal>(),
+            dec!(300)
+        );
+    }
+
+    #[test]
+    fn covering_more_than_shorted_is_an_error() {
+        let realized = match_fifo(&[
+            trade(TradeSide::Disposal, dec!(10), dec!(1500), 1),
+            trade(TradeSide::Acquisition, dec!(12), dec!(1440), 2),
+        ]);
+
+        assert_matches!(
+            realized,
+            Err(AccountingError::CoverExceedsShort { covered, shorted, .. })
+                if covered == dec!(12) && shorted == dec!(10)
+        );
+    }
+
+    #[test]
+    fn disposing_more_than_held_is_an_error() {
+        let realized = match_fifo(&[
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Disposal, dec!(11), dec!(1100), 2),
+        ]);
+
+        assert_matches!(
+            realized,
+            Err(AccountingError::DisposalExceedsHoldings { .. })
+        );
+    }
+
+    #[test]
+    fn lots_are_converted_at_their_own_dates() {
+        let mut lot = match_fifo(&[
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Disposal, dec!(10), dec!(1200), 4),
+        ])
+        .unwrap()
+        .remove(0);
+
+        lot.cost_basis.currency = FiatCurrency::EUR;
+        lot.proceeds.currency = FiatCurrency::EUR;
+
+        let eur = AssetId::Currency(FiatCurrency::EUR);
+        let mut provider = StaticPriceProvider::default();
+        provider
+            .insert(
+                eur.to_owned(),
+                FiatCurrency::USD,
+                NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                dec!(1.2),
+            )
+            .insert(
+                eur,
+                FiatCurrency::USD,
+                NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+                dec!(1.1),
+            );
+
+        let converted = convert_lots_to_currency(&[lot], FiatCurrency::USD, &provider);
+
+        let converted = assert_ok!(converted);
+
+        assert_eq!(
+            converted[0].cost_basis,
+            Money::new(dec!(1200), FiatCurrency::USD)
+        );
+        assert_eq!(
+            converted[0].proceeds,
+            Money::new(dec!(1320), FiatCurrency::USD)
+        );
+        assert_eq!(converted[0].gain(), dec!(120));
+    }
+
+    #[test]
+    fn fully_sold_position_is_closed() {
+        let trade_tx = |asset_kind, cash_kind, quantity, cash, day| {
+            let executed_at = Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap();
+
+            TransactionBuilder::default()
+                .add_operation(Operation {
+                    id: format!("OP{}A", day).parse().unwrap(),
+                    executed_at,
+                    ..operation(asset_kind, aapl(), quantity)
+                })
+                .add_operation(Operation {
+                    id: format!("OP{}B", day).parse().unwrap(),
+                    executed_at,
+                    ..operation(cash_kind, AssetId::Currency(FiatCurrency::USD), cash)
+                })
+                .build()
+                .unwrap()
+        };
+        let buy = |quantity, cash, day| {
+            trade_tx(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                quantity,
+                cash,
+                day,
+            )
+        };
+        let sell = |quantity, cash, day| {
+            trade_tx(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                OperationKind::Inflow(InflowOperation::Deposit),
+                quantity,
+                cash,
+                day,
+            )
+        };
+
+        let txs = [
+            buy(dec!(10), dec!(1000), 4),
+            buy(dec!(5), dec!(600), 5),
+            sell(dec!(15), dec!(1800), 11),
+            // reopened and still held at the end of the range
+            buy(dec!(2), dec!(250), 12),
+        ];
+        let range = (
+            Utc.with_ymd_and_hms(2021, 1, 10, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2021, 1, 20, 0, 0, 0).unwrap(),
+        );
+
+        let closed = closed_positions(&txs, range, CostBasisMethod::Fifo).unwrap();
+
+        assert_eq!(closed, vec![ClosedPosition {
+            asset: aapl(),
+            quantity: dec!(15),
+            opened_at: Utc.with_ymd_and_hms(2021, 1, 4, 10, 0, 0).unwrap(),
+            closed_at: Utc.with_ymd_and_hms(2021, 1, 11, 10, 0, 0).unwrap(),
+            cost_basis: Money::new(dec!(1600), FiatCurrency::USD),
+            proceeds: Money::new(dec!(1800), FiatCurrency::USD),
+        }]);
+        assert_eq!(closed[0].gain(), dec!(200));
+
+        let before_range = (range.0, Utc.with_ymd_and_hms(2021, 1, 11, 0, 0, 0).unwrap());
+
+        assert_eq!(
+            closed_positions(&txs, before_range, CostBasisMethod::Fifo),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn realized_lot_refers_to_the_disposal() {
+        let trade_tx = |id: &str, asset_kind, cash_kind, cash, day| {
+            let executed_at = Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap();
+
+            TransactionBuilder::default()
+                .add_operation(Operation {
+                    id: format!("{}A", id).parse().unwrap(),
+                    executed_at,
+                    ..operation(asset_kind, aapl(), dec!(10))
+                })
+                .add_operation(Operation {
+                    id: format!("{}B", id).parse().unwrap(),
+                    executed_at,
+                    ..operation(cash_kind, AssetId::Currency(FiatCurrency::USD), cash)
+                })
+                .build()
+                .unwrap()
+        };
+        let purchase = trade_tx(
+            "BUY",
+            OperationKind::Inflow(InflowOperation::Deposit),
+            OperationKind::Outflow(OutflowOperation::Withdrawal),
+            dec!(1000),
+            1,
+        );
+        let sale = trade_tx(
+            "SELL",
+            OperationKind::Outflow(OutflowOperation::Withdrawal),
+            OperationKind::Inflow(InflowOperation::Deposit),
+            dec!(1200),
+            4,
+        );
+
+        let realized = match_fifo(
+            &[&purchase, &sale].map(|tx| Trade::from_transaction(tx).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(realized.len(), 1);
+        assert_eq!(realized[0].transaction_id, Some(sale.id()));
+        assert_ne!(realized[0].transaction_id, Some(purchase.id()));
+    }
+
+    #[test]
+    fn same_day_trades_are_netted_before_fifo() {
+        let trades = [
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Acquisition, dec!(5), dec!(600), 5),
+            trade(TradeSide::Disposal, dec!(5), dec!(650), 5),
+        ];
+
+        let fifo = match_fifo_with_same_day_rule(&trades, None::<&Utc>).unwrap();
+
+        assert_eq!(fifo.len(), 1);
+        assert_eq!(fifo[0].cost_basis.amount, dec!(500));
+        assert_eq!(fifo[0].gain(), dec!(150));
+
+        let netting = net_same_day(&trades[.. 2], &trades[2 ..], &Utc);
+
+        assert_eq!(netting.trades.len(), 1);
+        assert_eq!(netting.trades[0].quantity, dec!(10));
+        assert_eq!(netting.realized.len(), 1);
+        assert_eq!(netting.realized[0].cost_basis.amount, dec!(600));
+        assert_eq!(netting.realized[0].gain(), dec!(50));
+
+        assert_eq!(
+            match_fifo_with_same_day_rule(&trades, Some(&Utc)).unwrap(),
+            netting.realized
+        );
+    }
+
+    #[test]
+    fn recorded_rate_is_preferred_over_the_provider() {
+        let in_eur = |side, value, day, rate| Trade {
+            value: Money::new(value, FiatCurrency::EUR),
+            fx_rate: Some(Money::new(rate, FiatCurrency::USD)),
+            ..trade(side, dec!(10), Decimal::ZERO, day)
+        };
+        let lots = match_fifo(&[
+            in_eur(TradeSide::Acquisition, dec!(1000), 1, dec!(1.25)),
+            Trade {
+                fx_rate: None,
+                ..in_eur(TradeSide::Disposal, dec!(1200), 4, Decimal::ZERO)
+            },
+        ])
+        .unwrap();
+
+        let mut provider = StaticPriceProvider::default();
+        provider.insert(
+            AssetId::Currency(FiatCurrency::EUR),
+            FiatCurrency::USD,
+            NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+            dec!(1.1),
+        );
+
+        let converted = assert_ok!(convert_lots_to_currency(
+            &lots,
+            FiatCurrency::USD,
+            &provider
+        ));
+
+        assert_eq!(
+            converted[0].cost_basis,
+            Money::new(dec!(1250), FiatCurrency::USD)
+        );
+        assert_eq!(
+            converted[0].proceeds,
+            Money::new(dec!(1320), FiatCurrency::USD)
+        );
+    }
+
+    #[test]
+    fn each_lot_consumed_is_converted_at_its_own_rate() {
+        let in_eur = |side, quantity, value, day, rate| Trade {
+            value: Money::new(value, FiatCurrency::EUR),
+            fx_rate: Some(Money::new(rate, FiatCurrency::USD)),
+            ..trade(side, quantity, Decimal::ZERO, day)
+        };
+        let lots = match_fifo(&[
+            in_eur(TradeSide::Acquisition, dec!(10), dec!(1000), 1, dec!(1.25)),
+            in_eur(TradeSide::Acquisition, dec!(10), dec!(1000), 2, dec!(1.5)),
+            in_eur(TradeSide::Disposal, dec!(15), dec!(1800), 4, dec!(1.1)),
+        ])
+        .unwrap();
+
+        let converted = assert_ok!(convert_lots_to_currency(
+            &lots,
+            FiatCurrency::USD,
+            &StaticPriceProvider::default()
+        ));
+
+        assert_eq!(
+            converted[0].cost_basis,
+            Money::new(dec!(2000), FiatCurrency::USD)
+        );
+        assert_eq!(
+            converted[0]
+                .consumed_from
+                .iter()
+                .map(|lot| lot.value)
+                .collect::<Vec<_>>(),
+            vec![dec!(1250), dec!(750)]
+        );
+        assert_eq!(
+            converted[0].proceeds,
+            Money::new(dec!(1980), FiatCurrency::USD)
+        );
+    }
+
+    #[test]
+    fn missing_rate_is_reported_with_its_date() {
+        let mut lot = match_fifo(&[
+            trade(TradeSide::Acquisition, dec!(10), dec!(1000), 1),
+            trade(TradeSide::Disposal, dec!(10), dec!(1200), 4),
+        ])
+        .unwrap()
+        .remove(0);
+
+        lot.cost_basis.currency = FiatCurrency::EUR;
+
+        let converted = convert_lots_to_currency(
+            &[lot],
+            FiatCurrency::USD,
+            &StaticPriceProvider::default(),
+        );
+
+        assert_eq!(
+            converted,
+            Err(ConversionError::MissingRate {
+                from: FiatCurrency::EUR,
+                to: FiatCurrency::USD,
+                date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            })
+        );
+    }
+}
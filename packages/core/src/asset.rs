@@ -1,10 +1,18 @@
-use core::fmt;
-use std::str::FromStr;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
-use regex::Regex;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Clone, Debug)]
+/// Asset with a display name.
+///
+/// `PartialEq` compares all fields, so the same asset named differently by
+/// two data sources isn't equal; use [`Asset::same_identity`] to compare ids
+/// only.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Asset {
     id: AssetId,
     name: AssetName,
@@ -14,9 +22,26 @@ impl Asset {
     pub fn new(id: AssetId, name: AssetName) -> Self {
         Self { id, name }
     }
+
+    pub fn id(&self) -> &AssetId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &AssetName {
+        &self.name
+    }
+
+    pub fn class(&self) -> AssetClass {
+        self.id.class()
+    }
+
+    /// Whether both assets have the same id, whatever their names.
+    pub fn same_identity(&self, other: &Asset) -> bool {
+        self.id == other.id
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum AssetId {
     Security(ISIN),
     Token(TokenId),
@@ -39,18 +64,30 @@ pub type AssetName = String;
 /// let isin = "A-000K0VF05".parse::<ISIN>();
 /// assert!(matches!(isin.unwrap_err(), ISINError::InvalidISO6166));
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "String")]
 pub struct ISIN(String);
 
 #[derive(Debug, Error)]
 pub enum ISINError {
-    #[error("Invalid regex")]
-    Regex,
-
     #[error("Invalid ISO 6166")]
     InvalidISO6166,
 }
 
+/// Naive check for ISO 6166-compatible value: two letters followed by ten
+/// letters or digits.
+///
+/// Digits are limited to ASCII, so that no other Unicode digit slips through.
+fn is_iso6166(value: &str) -> bool {
+    let bytes = value.as_bytes();
+
+    bytes.len() == 12
+        && bytes[.. 2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2 ..]
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
 impl FromStr for ISIN {
     type Err = ISINError;
 
@@ -59,27 +96,280 @@ impl FromStr for ISIN {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let normalized_value = s.replace('-', "");
 
-        // Naive regex for ISO 6166-compatible value
-        let iso6166_regex = r"^[A-Z]{2}[\dA-Z]{10}$"
-            .parse::<Regex>()
-            .map_err(|_| ISINError::Regex)?;
-
-        if !iso6166_regex.is_match(&normalized_value) {
+        if !is_iso6166(&normalized_value) {
             return Err(ISINError::InvalidISO6166);
         }
 
-        Ok(ISIN(s.into()))
+        Ok(ISIN(normalized_value))
+    }
+}
+
+impl TryFrom<String> for ISIN {
+    type Error = ISINError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
 
+impl ISIN {
+    /// Normalized value, without any dashes.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// ISO 3166-1 alpha-2 code of the issuing country.
+    pub fn country_code(&self) -> &str {
+        &self.0[.. 2]
+    }
+
+    /// National Securities Identifying Number, i.e. CUSIP for US securities.
+    pub fn nsin(&self) -> &str {
+        &self.0[2 .. 11]
+    }
+}
+
+impl fmt::Display for ISIN {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Parses every ISIN of a list, i.e. of a security master file, pairing each
+/// input with its result so that all the invalid ones are reported at once.
+pub fn validate_isins<'a>(
+    iter: impl Iterator<Item = &'a str>,
+) -> Vec<(String, Result<ISIN, ISINError>)> {
+    iter.map(|input| (input.to_owned(), input.parse::<ISIN>()))
+        .collect()
+}
+
+/// Identifier of a security in any of the schemes data sources use, see
+/// [`resolve_security`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SecurityId {
+    Isin(ISIN),
+    /// Committee on Uniform Securities Identification Procedures number of
+    /// a North American security, i.e. `037833100`.
+    Cusip(String),
+    /// Stock Exchange Daily Official List number of a UK or Irish security,
+    /// i.e. `0263494`.
+    Sedol(String),
+    /// Symbol on an exchange, i.e. `AAPL`, which doesn't identify a security
+    /// by itself, so has to be resolved (i.e. by a
+    /// [`SymbolResolver`](crate::data_sources::exante::SymbolResolver)),
+    /// possibly on an exchange of the `country` given.
+    Ticker {
+        symbol: String,
+        country: Option<String>,
+    },
+}
+
+/// Whether the value looks like an exchange symbol: up to 20 letters, digits,
+/// dots and dashes, starting with a letter or a digit.
+fn is_ticker(value: &str) -> bool {
+    let is_symbol_char = |b: &u8| b.is_ascii_uppercase() || b.is_ascii_digit();
+
+    match value.as_bytes() {
+        [first, rest @ ..] if rest.len() < 20 => {
+            is_symbol_char(first)
+                && rest
+                    .iter()
+                    .all(|b| is_symbol_char(b) || *b == b'.' || *b == b'-')
+        }
+        _ => false,
+    }
+}
+
+/// Tells which kind of identifier a raw value is, trying an ISIN, then a
+/// CUSIP, then a SEDOL, and taking anything else looking like a symbol for a
+/// ticker. `None` when the value can't be any of them.
+///
+/// Values are matched ignoring case and surrounding whitespace. CUSIPs and
+/// SEDOLs are only recognized with a valid check digit, so that a ticker of
+/// the same length isn't taken for one. The `country_hint`, an ISO 3166-1
+/// alpha-2 code, goes with a ticker to resolve.
+pub fn resolve_security(raw: &str, country_hint: Option<&str>) -> Option<SecurityId> {
+    let value = raw.trim().to_uppercase();
+
+    if let Ok(isin) = value.parse::<ISIN>() {
+        Some(SecurityId::Isin(isin))
+    } else if is_cusip(&value) {
+        Some(SecurityId::Cusip(value))
+    } else if is_sedol(&value) {
+        Some(SecurityId::Sedol(value))
+    } else if is_ticker(&value) {
+        Some(SecurityId::Ticker {
+            symbol: value,
+            country: country_hint.map(str::to_uppercase),
+        })
+    } else {
+        None
+    }
+}
+
+/// Value of a character in a check digit, letters following digits.
+fn check_value(c: char) -> Option<u32> {
+    c.to_digit(36)
+}
+
+fn is_cusip(value: &str) -> bool {
+    let chars = value.chars().collect::<Vec<_>>();
+
+    if chars.len() != 9 {
+        return false;
+    }
+
+    let [body @ .., check] = chars.as_slice() else {
+        return false;
+    };
+
+    let sum = body.iter().enumerate().try_fold(0, |sum, (i, &c)| {
+        let value = match c {
+            '*' => 36,
+            '@' => 37,
+            '#' => 38,
+            c => check_value(c)?,
+        };
+        let value = if i % 2 == 1 { value * 2 } else { value };
+
+        Some(sum + value / 10 + value % 10)
+    });
+
+    sum.is_some_and(|sum| check.to_digit(10) == Some((10 - sum % 10) % 10))
+}
+
+fn is_sedol(value: &str) -> bool {
+    const WEIGHTS: [u32; 6] = [1, 3, 1, 7, 3, 9];
+
+    let chars = value.chars().collect::<Vec<_>>();
+
+    if chars.len() != 7 {
+        return false;
+    }
+
+    let [body @ .., check] = chars.as_slice() else {
+        return false;
+    };
+
+    // vowels are left out, so that no word is made
+    if body.iter().any(|c| "AEIOU".contains(*c)) {
+        return false;
+    }
+
+    let sum = body
+        .iter()
+        .zip(WEIGHTS)
+        .try_fold(0, |sum, (&c, weight)| Some(sum + check_value(c)? * weight));
+
+    sum.is_some_and(|sum| check.to_digit(10) == Some((10 - sum % 10) % 10))
+}
+
+/// Broad kind of an asset, as used in reports.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum AssetClass {
+    Fiat,
+    Security,
+    Token,
+}
+
+impl AssetId {
+    pub fn class(&self) -> AssetClass {
+        match self {
+            AssetId::Security(_) => AssetClass::Security,
+            AssetId::Token(_) => AssetClass::Token,
+            AssetId::Currency(_) => AssetClass::Fiat,
+        }
+    }
+
+    /// Number of decimal places amounts of the asset are kept at: the minor
+    /// units for fiat currencies, 8 for everything else.
+    pub fn scale(&self) -> u32 {
+        match self {
+            AssetId::Currency(currency) => currency.minor_units(),
+            AssetId::Security(_) | AssetId::Token(_) => 8,
+        }
+    }
+
+    /// Rounds the amount to the asset's scale with banker's rounding
+    /// (half to even) and trims trailing zeros.
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        amount.round_dp(self.scale()).normalize()
+    }
+}
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetId::Security(isin) => fmt::Display::fmt(isin, f),
+            AssetId::Token(token_id) => f.write_str(&token_id.0),
+            AssetId::Currency(currency) => fmt::Display::fmt(currency, f),
+        }
+    }
+}
+
+/// Sum of amounts of an asset exceeding what `Decimal` can hold.
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("Sum of {asset} amounts overflows")]
+pub struct SumOverflow {
+    pub asset: AssetId,
+}
+
+/// Adds `amount` to the total of `asset`, failing instead of panicking on
+/// overflow.
+#[cfg(feature = "std")]
+pub(crate) fn add_checked(
+    totals: &mut HashMap<AssetId, Decimal>,
+    asset: &AssetId,
+    amount: Decimal,
+) -> Result<(), SumOverflow> {
+    let total = totals.entry(asset.to_owned()).or_default();
+
+    *total = total.checked_add(amount).ok_or_else(|| SumOverflow {
+        asset: asset.to_owned(),
+    })?;
+
+    Ok(())
+}
+
 /// Token ID
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct TokenId(pub String);
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum FiatCurrency {
     USD,
     EUR,
+    JPY,
+}
+
+impl FiatCurrency {
+    /// Number of decimal places of the currency's minor unit, as per
+    /// ISO 4217, i.e. 2 for cents.
+    pub fn minor_units(&self) -> u32 {
+        match self {
+            FiatCurrency::USD | FiatCurrency::EUR => 2,
+            FiatCurrency::JPY => 0,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Unsupported currency: {0}")]
+pub struct UnsupportedCurrency(pub String);
+
+impl FromStr for FiatCurrency {
+    type Err = UnsupportedCurrency;
+
+    /// Parses an ISO 4217 code, i.e. `USD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "USD" => Ok(FiatCurrency::USD),
+            "EUR" => Ok(FiatCurrency::EUR),
+            "JPY" => Ok(FiatCurrency::JPY),
+            code => Err(UnsupportedCurrency(code.to_owned())),
+        }
+    }
 }
 
 impl fmt::Display for FiatCurrency {
@@ -88,9 +378,37 @@ impl fmt::Display for FiatCurrency {
     }
 }
 
+/// An amount expressed in a fiat currency.
+///
+/// # Example
+/// ```
+/// use delfin::asset::{FiatCurrency, Money};
+/// use rust_decimal_macros::dec;
+///
+/// let price = Money::new(dec!(12.50), FiatCurrency::USD);
+/// assert_eq!(price.to_string(), "12.50 USD");
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: FiatCurrency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: FiatCurrency) -> Self {
+        Self { amount, currency }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use claim::{assert_err, assert_ok};
+    use claim::{assert_err, assert_matches, assert_ok};
 
     use super::*;
 
@@ -108,6 +426,75 @@ mod test {
         });
     }
 
+    #[test]
+    fn isin_exposes_its_parts() {
+        for isin_number in ["US-000402625-0", "US0004026250"] {
+            let isin = isin_number.parse::<ISIN>().unwrap();
+
+            assert_eq!(isin.as_str(), "US0004026250");
+            assert_eq!(isin.country_code(), "US");
+            assert_eq!(isin.nsin(), "000402625");
+            assert_eq!(isin.as_str().parse::<ISIN>().unwrap(), isin);
+        }
+    }
+
+    #[test]
+    fn security_identifiers_are_told_apart() {
+        assert_eq!(
+            resolve_security("US0378331005", None),
+            Some(SecurityId::Isin("US0378331005".parse().unwrap()))
+        );
+        assert_eq!(
+            resolve_security(" 037833100 ", Some("US")),
+            Some(SecurityId::Cusip("037833100".into()))
+        );
+        assert_eq!(
+            resolve_security("0263494", Some("GB")),
+            Some(SecurityId::Sedol("0263494".into()))
+        );
+        assert_eq!(
+            resolve_security("aapl", Some("us")),
+            Some(SecurityId::Ticker {
+                symbol: "AAPL".into(),
+                country: Some("US".into()),
+            })
+        );
+        // check digits don't match, so just symbols
+        assert_matches!(
+            resolve_security("037833101", None),
+            Some(SecurityId::Ticker { .. })
+        );
+        assert_matches!(
+            resolve_security("0263495", None),
+            Some(SecurityId::Ticker { .. })
+        );
+        assert_eq!(resolve_security("", None), None);
+        assert_eq!(resolve_security("AAPL US", None), None);
+    }
+
+    #[test]
+    fn every_isin_of_a_list_is_validated() {
+        let results = validate_isins(
+            ["US0378331005", "US03783310!5", "NA-000K0VF05-4", ""].into_iter(),
+        );
+
+        let inputs = results
+            .iter()
+            .map(|(input, _)| input.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(inputs, vec![
+            "US0378331005",
+            "US03783310!5",
+            "NA-000K0VF05-4",
+            ""
+        ]);
+        assert_eq!(results[0].1.as_ref().unwrap().as_str(), "US0378331005");
+        assert_matches!(&results[1].1, Err(ISINError::InvalidISO6166));
+        assert_eq!(results[2].1.as_ref().unwrap().as_str(), "NA000K0VF054");
+        assert_matches!(&results[3].1, Err(ISINError::InvalidISO6166));
+    }
+
     #[test]
     fn cannot_parse_invalid_isin_input() {
         let valid_isin_numbers = vec![
@@ -134,6 +521,7 @@ mod prop_tests {
         Fake,
     };
     use quickcheck::Arbitrary;
+    use rust_decimal_macros::dec;
 
     use super::*;
 
@@ -142,8 +530,9 @@ mod prop_tests {
             g.choose(&[
                 AssetId::Currency(FiatCurrency::EUR),
                 AssetId::Currency(FiatCurrency::USD),
-                AssetId::Token(TokenId(NumberWithFormat(&"0x####...####").fake())),
-                AssetId::Security(ISIN(NumberWithFormat(&"###-###-###").fake())),
+                AssetId::Currency(FiatCurrency::JPY),
+                AssetId::Token(TokenId(NumberWithFormat("0x####...####").fake())),
+                AssetId::Security(ISIN(NumberWithFormat("US##########").fake())),
             ])
             .unwrap()
             .to_owned()
@@ -175,4 +564,69 @@ mod prop_tests {
             quickcheck::empty_shrinker()
         }
     }
+
+    /// Input which is sometimes shaped like an ISIN, so that both rejected
+    /// and accepted inputs get covered.
+    #[derive(Clone, Debug)]
+    struct IsinLikeInput(String);
+
+    impl quickcheck::Arbitrary for IsinLikeInput {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let input = if bool::arbitrary(g) {
+                String::arbitrary(g)
+            } else {
+                let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-٣a "
+                    .chars()
+                    .collect::<Vec<_>>();
+
+                (0 .. 14).map(|_| *g.choose(&alphabet).unwrap()).collect()
+            };
+
+            Self(input)
+        }
+    }
+
+    #[test]
+    fn asset_id_maps_to_its_class() {
+        let isin = AssetId::Security("US0378331005".parse().unwrap());
+        let token = AssetId::Token(TokenId("BTC".into()));
+        let currency = AssetId::Currency(FiatCurrency::EUR);
+
+        assert_eq!(isin.class(), AssetClass::Security);
+        assert_eq!(token.class(), AssetClass::Token);
+        assert_eq!(currency.class(), AssetClass::Fiat);
+        assert_eq!(
+            Asset::new(token, "Bitcoin".into()).class(),
+            AssetClass::Token
+        );
+    }
+
+    #[test]
+    fn currency_minor_units_follow_iso_4217() {
+        assert_eq!(FiatCurrency::JPY.minor_units(), 0);
+        assert_eq!(FiatCurrency::USD.minor_units(), 2);
+        assert_eq!(
+            AssetId::Currency(FiatCurrency::JPY).round(dec!(1500.4)),
+            dec!(1500)
+        );
+    }
+
+    #[test]
+    fn asset_identity_ignores_name() {
+        let id = AssetId::Security("US0378331005".parse().unwrap());
+        let apple = Asset::new(id.to_owned(), "Apple Inc".into());
+        let apple_upper = Asset::new(id, "APPLE INC".into());
+
+        assert!(apple.same_identity(&apple_upper));
+        assert_ne!(apple, apple_upper);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn isin_parser_accepts_only_twelve_normalized_chars(input: IsinLikeInput) {
+        if let Ok(isin) = input.0.parse::<ISIN>() {
+            assert_eq!(isin.as_str().len(), 12);
+            assert!(isin.as_str().chars().all(|c| c.is_ascii_alphanumeric()));
+            assert_eq!(isin.nsin().len(), 9);
+        }
+    }
 }
@@ -1,4 +1,7 @@
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use crate::asset::Money;
 
 pub trait Asset {
     fn id(&self) -> String;
@@ -6,17 +9,29 @@ pub trait Asset {
     fn symbol(&self) -> String;
 }
 
-trait AssetDisposal {
+pub trait AssetDisposal {
     fn disposed_asset(&self) -> Box<dyn Asset>;
 
+    /// Amount of the disposed asset, i.e. number of shares sold.
+    fn quantity(&self) -> Decimal;
+
+    /// Proceeds received for the disposed quantity.
+    fn value(&self) -> Money;
+
     fn fee_asset(&self) -> Option<Box<dyn Asset>>;
 
     fn executed_at(&self) -> DateTime<Utc>;
 }
 
-trait AssetAcquisition {
+pub trait AssetAcquisition {
     fn acquired_asset(&self) -> Box<dyn Asset>;
 
+    /// Amount of the acquired asset, i.e. number of shares bought.
+    fn quantity(&self) -> Decimal;
+
+    /// Cost paid for the acquired quantity.
+    fn value(&self) -> Money;
+
     fn fee_asset(&self) -> Option<Box<dyn Asset>>;
 
     fn executed_at(&self) -> DateTime<Utc>;
@@ -24,4 +39,105 @@ trait AssetAcquisition {
 
 /// Exchange expects a single asset acquired, a single asset disposed,
 /// and up to one asset to capture a fee.
-trait AssetExchange: AssetDisposal + AssetAcquisition {}
+///
+/// Both sides carry their own `quantity` and `value`, so the exchange
+/// economics (i.e. the price paid per unit acquired) can be derived from
+/// `AssetDisposal::value` and `AssetAcquisition::quantity`. Since the method
+/// names overlap, call them with the fully qualified syntax.
+pub trait AssetExchange: AssetDisposal + AssetAcquisition {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::asset::FiatCurrency;
+
+    struct Ticker(&'static str);
+
+    impl Asset for Ticker {
+        fn id(&self) -> String {
+            self.0.to_owned()
+        }
+
+        fn symbol(&self) -> String {
+            self.0.to_owned()
+        }
+    }
+
+    /// Selling 1500 USD to buy 10 AAPL shares.
+    struct MockExchange;
+
+    impl AssetDisposal for MockExchange {
+        fn disposed_asset(&self) -> Box<dyn Asset> {
+            Box::new(Ticker("USD"))
+        }
+
+        fn quantity(&self) -> Decimal {
+            dec!(1500)
+        }
+
+        fn value(&self) -> Money {
+            Money::new(dec!(1500), FiatCurrency::USD)
+        }
+
+        fn fee_asset(&self) -> Option<Box<dyn Asset>> {
+            None
+        }
+
+        fn executed_at(&self) -> DateTime<Utc> {
+            Utc.with_ymd_and_hms(2021, 1, 5, 15, 30, 0).unwrap()
+        }
+    }
+
+    impl AssetAcquisition for MockExchange {
+        fn acquired_asset(&self) -> Box<dyn Asset> {
+            Box::new(Ticker("AAPL"))
+        }
+
+        fn quantity(&self) -> Decimal {
+            dec!(10)
+        }
+
+        fn value(&self) -> Money {
+            Money::new(dec!(1500), FiatCurrency::USD)
+        }
+
+        fn fee_asset(&self) -> Option<Box<dyn Asset>> {
+            None
+        }
+
+        fn executed_at(&self) -> DateTime<Utc> {
+            Utc.with_ymd_and_hms(2021, 1, 5, 15, 30, 0).unwrap()
+        }
+    }
+
+    impl AssetExchange for MockExchange {}
+
+    fn unit_price(exchange: &impl AssetExchange) -> Money {
+        let paid = AssetDisposal::value(exchange);
+
+        Money::new(
+            paid.amount / AssetAcquisition::quantity(exchange),
+            paid.currency,
+        )
+    }
+
+    #[test]
+    fn exchange_exposes_quantities_and_values() {
+        let exchange = MockExchange;
+
+        assert_eq!(AssetDisposal::quantity(&exchange), dec!(1500));
+        assert_eq!(AssetAcquisition::quantity(&exchange), dec!(10));
+        assert_eq!(
+            AssetAcquisition::value(&exchange),
+            Money::new(dec!(1500), FiatCurrency::USD)
+        );
+        assert_eq!(exchange.acquired_asset().symbol(), "AAPL");
+        assert_eq!(
+            unit_price(&exchange),
+            Money::new(dec!(150), FiatCurrency::USD)
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    error::Error,
+    fmt::Debug,
+    io::{self, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+use chrono::{DateTime, Utc};
+use csv::ReaderBuilder;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use slice_group_by::GroupBy;
+use thiserror::Error;
+
+use crate::{
+    asset::{Asset, AssetId, FiatCurrency, Money},
+    data_sources::{combine_split_datetime, open_file, skip_preamble, SourceRecord},
+    ledger::Ledger,
+    operation::{
+        operation_kind_from, InflowOperation, Operation, OperationId, OperationIdError,
+        OperationKind, OutflowOperation,
+    },
+    transaction::{Fingerprint, Transaction},
+};
+
+/// Ledger of all the operations imported from DEGIRO, as `Account.csv` has no
+/// account number.
+const DEGIRO_LEDGER: &str = "DEGIRO";
+
+const DEGIRO_DATE_FORMAT: &str = "%d-%m-%Y";
+
+/// Currency of the account, to which the `FX` column converts.
+const DEGIRO_ACCOUNT_CURRENCY: FiatCurrency = FiatCurrency::EUR;
+
+/// Columns identifying the header line, see [`skip_preamble`].
+const HEADER_COLUMNS: [&str; 4] = ["Date", "Product", "ISIN", "Description"];
+
+/// Reads all records from DEGIRO's `Account.csv`, decompressing it if it's
+/// gzipped, see [`open_file`].
+pub fn read_csv_file<TPath>(file_path: TPath) -> Result<Vec<RawRecord>, Box<dyn Error>>
+where
+    TPath: AsRef<Path> + Debug,
+{
+    Ok(read_csv_reader(open_file(file_path)?).collect())
+}
+
+/// Reads records one by one, skipping the ones which cannot be deserialized.
+///
+/// Columns are read by position, as the amounts of `Change` and `Balance`
+/// follow their currencies in columns without a header. Rows above the header
+/// are skipped, see [`skip_preamble`].
+pub fn read_csv_reader<R: io::Read>(reader: R) -> impl Iterator<Item = RawRecord> {
+    read_csv_rows(reader).filter_map(|record| record.ok())
+}
+
+/// Same as [`read_csv_reader`], but yields the rows which cannot be
+/// deserialized as errors instead of skipping them, i.e. to report how many
+/// were dropped.
+pub fn read_csv_rows<R: io::Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<RawRecord, csv::Error>> {
+    ReaderBuilder::new()
+        .from_reader(skip_preamble(BufReader::new(reader), &HEADER_COLUMNS))
+        .into_records()
+        .enumerate()
+        .map(|(row, record)| {
+            let mut record = record?.deserialize::<RawRecord>(None)?;
+            record.row = row + 1;
+
+            Ok(record)
+        })
+}
+
+/// Groups records into transactions.
+///
+/// Records executed at the same time for the same product make a transaction,
+/// so a dividend is paired with the tax withheld from it. Records which cannot
+/// be converted into operations are left out, see
+/// [`group_records_reporting_rejected`] to find out which ones.
+pub fn group_records_into_transactions(
+    records: &[RawRecord],
+) -> Result<Vec<Transaction>, RawRecordError> {
+    let (transactions, _) = group_records_reporting_rejected(records);
+
+    Ok(transactions)
+}
+
+/// Same as [`group_records_into_transactions`], but also returns the records
+/// left out of the transactions.
+pub fn group_records_reporting_rejected(
+    records: &[RawRecord],
+) -> (Vec<Transaction>, Vec<RejectedRecord>) {
+    let mut operations = vec![];
+    let mut rejected = vec![];
+
+    for record in records {
+        match record.split_record() {
+            Ok(record_operations) => operations.extend(
+                record_operations
+                    .into_iter()
+                    .map(|operation| (record.isin.as_str(), operation)),
+            ),
+            Err(error) => rejected.push(RejectedRecord {
+                row: record.row,
+                error,
+            }),
+        }
+    }
+
+    operations.sort_by(|(isin_a, a), (isin_b, b)| {
+        a.executed_at
+            .cmp(&b.executed_at)
+            .then_with(|| isin_a.cmp(isin_b))
+    });
+
+    let transactions = operations
+        .linear_group_by(|(isin_a, a), (isin_b, b)| {
+            a.executed_at == b.executed_at && isin_a == isin_b
+        })
+        .filter_map(|group| {
+            Transaction::from_operations(
+                group
+                    .iter()
+                    .map(|(_, operation)| operation.to_owned())
+                    .collect(),
+            )
+            .ok()
+        })
+        .collect();
+
+    (transactions, rejected)
+}
+
+/// Record which couldn't be converted into an operation.
+#[derive(Debug)]
+pub struct RejectedRecord {
+    /// Line of the record in the file, not counting the header.
+    pub row: usize,
+    pub error: RawRecordError,
+}
+
+/// Row of DEGIRO's `Account.csv`.
+#[derive(Debug, Deserialize)]
+pub struct RawRecord {
+    /// Line of the record in the file, not counting the header.
+    #[serde(skip)]
+    row: usize,
+
+    /// Date in the `dd-mm-yyyy` format.
+    date: String,
+
+    /// Time in the `HH:MM` format.
+    time: String,
+
+    #[allow(dead_code)]
+    value_date: String,
+
+    product: String,
+
+    isin: String,
+
+    description: String,
+
+    /// Rate of the exchange to the account's currency, as the amount of
+    /// `Change` currency a euro buys, i.e. `1,2034`.
+    fx: String,
+
+    change_currency: String,
+
+    /// Amount with a decimal comma, i.e. `-1.210,50`.
+    change: String,
+
+    balance_currency: String,
+
+    balance: String,
+
+    order_id: String,
+}
+
+#[derive(Error, Debug)]
+pub enum RawRecordError {
+    #[error("{0}")]
+    OperationId(#[from] OperationIdError),
+
+    #[error("Unsupported currency: {0}")]
+    Currency(String),
+
+    #[error("Invalid record value: {0}")]
+    Value(String),
+
+    #[error("Invalid record date: {0}")]
+    Date(#[from] chrono::ParseError),
+}
+
+impl SourceRecord for RawRecord {
+    type Error = RawRecordError;
+
+    fn operation(&self) -> Result<Operation, Self::Error> {
+        self.try_into()
+    }
+}
+
+impl RawRecord {
+    /// Id derived from the content of the record rather than its position, so
+    /// that it's the same in every download of the export. Records equal in
+    /// all of their columns get the same id.
+    pub fn id(&self) -> Result<OperationId, RawRecordError> {
+        let fingerprint = Fingerprint::of(
+            [
+                &self.date,
+                &self.time,
+                &self.product,
+                &self.isin,
+                &self.description,
+                &self.change_currency,
+                &self.change,
+                &self.balance_currency,
+                &self.balance,
+                &self.order_id,
+            ]
+            .map(|column| column.trim())
+            .join("|")
+            .as_bytes(),
+        );
+
+        Ok(format!("{}-{}", DEGIRO_LEDGER, fingerprint).parse::<OperationId>()?)
+    }
+
+    /// Price of a unit of the `Change` currency in euros, from the `FX`
+    /// column.
+    pub fn fx_rate(&self) -> Option<Money> {
+        let fx = parse_european_decimal(&self.fx).filter(|fx| !fx.is_zero())?;
+
+        Some(Money::new(Decimal::ONE / fx, DEGIRO_ACCOUNT_CURRENCY))
+    }
+
+    pub fn executed_at(&self) -> Result<DateTime<Utc>, RawRecordError> {
+        Ok(combine_split_datetime(
+            &self.date,
+            &self.time,
+            DEGIRO_DATE_FORMAT,
+        )?)
+    }
+}
+
+impl TryFrom<&RawRecord> for Operation {
+    type Error = RawRecordError;
+
+    fn try_from(record: &RawRecord) -> Result<Operation, Self::Error> {
+        let change = parse_european_decimal(&record.change)
+            .ok_or_else(|| RawRecordError::Value(record.change.to_owned()))?;
+
+        let category = match record.description.as_str() {
+            "Dividend" => "dividend",
+            "Dividend Tax" => "tax",
+            "DEGIRO Transaction Fee" => "commission",
+            // deposits and withdrawals, or a description we don't know yet
+            _ => "",
+        };
+        let kind = match record.description.as_str() {
+            // cash leg of a trade, whose asset leg isn't in the export
+            "Transaction" if change > Decimal::ZERO => {
+                OperationKind::Inflow(InflowOperation::Sale)
+            }
+            "Transaction" => OperationKind::Outflow(OutflowOperation::Purchase),
+            _ => operation_kind_from(change, category),
+        };
+
+        let currency = match record.change_currency.as_str() {
+            "EUR" => FiatCurrency::EUR,
+            "USD" => FiatCurrency::USD,
+            "JPY" => FiatCurrency::JPY,
+            currency => return Err(RawRecordError::Currency(currency.to_owned())),
+        };
+
+        Ok(Operation {
+            id: record.id()?,
+            kind,
+            ledger: Ledger::canonical(DEGIRO_LEDGER),
+            asset: Asset::new(
+                AssetId::Currency(currency),
+                record.change_currency.to_owned(),
+            ),
+            value: change.abs(),
+            executed_at: record.executed_at()?,
+            description: (!record.product.is_empty()).then(|| record.product.to_owned()),
+            source_type: Some(record.description.to_owned()),
+            fx_rate: record.fx_rate(),
+            counterparty: None,
+        })
+    }
+}
+
+/// Parses an amount written with a decimal comma and dots separating
+/// thousands, i.e. `-1.210,50`.
+pub fn parse_european_decimal(s: &str) -> Option<Decimal> {
+    Decimal::from_str(&s.trim().replace('.', "").replace(',', ".")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_matches};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::operation::FeeCategory;
+
+    static DEMO_CSV_FILE_PATH: &str = "input/degiro/demo.csv";
+    static PREAMBLE_CSV_FILE_PATH: &str = "input/degiro/preamble.csv";
+
+    #[test]
+    fn parse_european_decimals() {
+        assert_eq!(parse_european_decimal("-1.210,50"), Some(dec!(-1210.50)));
+        assert_eq!(parse_european_decimal("0,31"), Some(dec!(0.31)));
+        assert_eq!(parse_european_decimal("n/a"), None);
+    }
+
+    #[test]
+    fn preamble_rows_are_skipped() {
+        let records = read_csv_file(PREAMBLE_CSV_FILE_PATH).unwrap();
+
+        assert_eq!(records.len(), 5);
+        assert_eq!(group_records_into_transactions(&records).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn negative_fee_is_a_rebate() {
+        let csv = "Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+15-03-2021,09:04,15-03-2021,APPLE INC. - COMMON ST,US0378331005,DEGIRO Transaction Fee,,EUR,\"-0,50\",EUR,\"99,50\",
+16-03-2021,09:04,16-03-2021,APPLE INC. - COMMON ST,US0378331005,DEGIRO Transaction Fee,,EUR,\"0,50\",EUR,\"100,00\",
+";
+
+        let operations = read_csv_reader(csv.as_bytes())
+            .map(|record| Operation::try_from(&record).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            operations[0].kind,
+            OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            })
+        );
+        assert_eq!(
+            operations[1].kind,
+            OperationKind::Inflow(InflowOperation::Rebate)
+        );
+        assert_eq!(operations[1].value, dec!(0.50));
+    }
+
+    #[test]
+    fn malformed_record_is_rejected_alone() {
+        let csv = "Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+15-03-2021,09:04,15-03-2021,APPLE INC. - COMMON ST,US0378331005,DEGIRO Transaction Fee,,EUR,\"-0,50\",EUR,\"99,50\",
+16-03-2021,09:04,16-03-2021,APPLE INC. - COMMON ST,US0378331005,Dividend,,XYZ,\"1,00\",XYZ,\"1,00\",
+";
+        let records = read_csv_reader(csv.as_bytes()).collect::<Vec<_>>();
+
+        let (transactions, rejected) = group_records_reporting_rejected(&records);
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].row, records[1].row);
+        assert_matches!(&rejected[0].error, RawRecordError::Currency(_));
+    }
+
+    #[test]
+    fn unreadable_row_is_yielded_as_an_error() {
+        let csv = "Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+15-03-2021,09:04,15-03-2021,APPLE INC. - COMMON ST,US0378331005,DEGIRO Transaction Fee,,EUR,\"-0,50\",EUR
+16-03-2021,09:04,16-03-2021,APPLE INC. - COMMON ST,US0378331005,Dividend,,USD,\"1,00\",USD,\"1,00\",
+";
+        let rows = read_csv_rows(csv.as_bytes()).collect::<Vec<_>>();
+
+        assert_eq!(rows.len(), 2);
+        assert_err!(&rows[0]);
+        assert_eq!(rows[1].as_ref().unwrap().row, 2);
+        assert_eq!(read_csv_reader(csv.as_bytes()).count(), 1);
+    }
+
+    #[test]
+    fn ids_dont_depend_on_the_position_of_records() {
+        let records = read_csv_file(DEMO_CSV_FILE_PATH).unwrap();
+        let ids = |records: &[RawRecord]| {
+            group_records_into_transactions(records)
+                .unwrap()
+                .iter()
+                .flat_map(|tx| tx.operations().iter().map(|op| op.id.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let all_ids = ids(&records);
+        let later_ids = ids(&records[1 ..]);
+
+        assert!(later_ids.iter().all(|id| all_ids.contains(id)));
+    }
+
+    #[test]
+    fn transaction_is_the_cash_leg_of_a_trade() {
+        let csv = "Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+15-03-2021,09:04,15-03-2021,APPLE INC. - COMMON ST,US0378331005,Transaction,,EUR,\"-1.210,50\",EUR,\"99,50\",abc
+16-03-2021,09:04,16-03-2021,APPLE INC. - COMMON ST,US0378331005,Transaction,,EUR,\"1.300,00\",EUR,\"1.399,50\",def
+";
+
+        let operations = read_csv_reader(csv.as_bytes())
+            .map(|record| Operation::try_from(&record).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            operations[0].kind,
+            OperationKind::Outflow(OutflowOperation::Purchase)
+        );
+        assert_eq!(operations[0].value, dec!(1210.50));
+        assert_eq!(
+            operations[1].kind,
+            OperationKind::Inflow(InflowOperation::Sale)
+        );
+    }
+
+    #[test]
+    fn dividend_is_paired_with_its_tax() {
+        let records = read_csv_file(DEMO_CSV_FILE_PATH).unwrap();
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        assert_eq!(records.len(), 5);
+        assert_eq!(transactions.len(), 3);
+
+        let dividend_tx = transactions
+            .iter()
+            .find(|tx| {
+                tx.operations()
+                    .iter()
+                    .any(|op| op.kind == OperationKind::Inflow(InflowOperation::Dividend))
+            })
+            .unwrap();
+
+        assert_eq!(dividend_tx.operations().len(), 2);
+        assert_eq!(
+            dividend_tx.net_value_by_asset().unwrap()
+                [&AssetId::Currency(FiatCurrency::USD)],
+            dec!(1.74)
+        );
+    }
+}
@@ -1,85 +1,549 @@
-use std::{error::Error, fmt::Debug, fs, path::Path};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
+    error::Error,
+    fmt::Debug,
+    io::{self, BufRead},
+    path::Path,
+};
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use csv::ReaderBuilder;
-use serde::{Deserialize, Deserializer};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize};
 use slice_group_by::GroupBy;
 use thiserror::Error;
 
 use crate::{
-    asset::{Asset, AssetId, FiatCurrency, ISINError, ISIN},
-    ledger::Ledger,
+    asset::{Asset, AssetClass, AssetId, FiatCurrency, ISINError, ISIN},
+    data_sources::{open_file, skip_preamble, Trailer},
+    ledger::{CaseFolding, Ledger},
     operation::{
-        InflowOperation, Operation, OperationId, OperationIdError, OperationKind,
-        OutflowOperation,
+        operation_kind_from_direction, InflowOperation, Operation, OperationId,
+        OperationIdError, OperationKind, OutflowOperation,
     },
-    transaction::{Transaction, TransactionBuilder},
+    sink::{SinkError, TransactionSink},
+    transaction::{Fingerprint, Transaction, TransactionBuilder},
 };
 
+/// Reads all records from the file, decompressing it if it's gzipped, see
+/// [`open_file`].
 pub fn read_csv_file<TPath>(file_path: TPath) -> Result<Vec<RawRecord>, Box<dyn Error>>
 where
     TPath: AsRef<Path> + Debug,
 {
-    let data = fs::read_to_string(file_path)?;
+    Ok(read_csv_reader(open_file(file_path)?).collect())
+}
+
+/// Operation type of both legs of a trade.
+const TRADE_OPERATION_TYPE: &str = "TRADE";
+
+/// Operation type of a move between two accounts of the user, whose comment
+/// ends with the other account, i.e. `Transfer to ABC1234.002`.
+const TRANSFER_OPERATION_TYPE: &str = "TRANSFER";
 
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(b'\t')
-        .from_reader(data.as_bytes());
+/// Columns identifying the header line, see [`skip_preamble`].
+const HEADER_COLUMNS: [&str; 3] = ["Transaction ID", "Operation type", "UUID"];
 
-    let records = rdr
-        .deserialize::<RawRecord>()
-        .filter_map(|record| record.ok())
-        .collect();
+/// Delimiters recognized by [`detect_delimiter`], in order of preference.
+const DELIMITER_CANDIDATES: [u8; 3] = [b'\t', b',', b';'];
+
+/// Reads records one by one, skipping the ones which cannot be deserialized.
+///
+/// Rows above the header are skipped, see [`skip_preamble`]. The delimiter is
+/// detected from the header line, see [`detect_delimiter`].
+pub fn read_csv_reader<R: io::Read>(reader: R) -> impl Iterator<Item = RawRecord> {
+    read_csv_reader_with_delimiter(reader, None)
+}
+
+/// Same as [`read_csv_reader`], but with an explicit delimiter. The delimiter
+/// is detected only when `None` is given.
+pub fn read_csv_reader_with_delimiter<R: io::Read>(
+    reader: R,
+    delimiter: Option<u8>,
+) -> impl Iterator<Item = RawRecord> {
+    read_csv_rows(reader, delimiter).filter_map(|record| record.ok())
+}
+
+/// Same as [`read_csv_reader_with_delimiter`], but yields the rows which
+/// cannot be deserialized as errors instead of skipping them, i.e. to report
+/// how many were dropped.
+pub fn read_csv_rows<R: io::Read>(
+    reader: R,
+    delimiter: Option<u8>,
+) -> impl Iterator<Item = Result<RawRecord, csv::Error>> {
+    let mut reader = skip_preamble(io::BufReader::new(reader), &HEADER_COLUMNS);
+    let delimiter = delimiter.unwrap_or_else(|| {
+        reader
+            .fill_buf()
+            .map(detect_delimiter)
+            .unwrap_or(DELIMITER_CANDIDATES[0])
+    });
+
+    ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(reader)
+        .into_deserialize::<RawRecord>()
+}
+
+/// Guesses the delimiter of a CSV file from a sample of its beginning.
+///
+/// The candidate occurring most often in the first line wins, ties going to
+/// the earlier of tab, comma and semicolon. Falls back to tab.
+pub fn detect_delimiter(sample: &[u8]) -> u8 {
+    let header = sample
+        .split(|&byte| byte == b'\n')
+        .next()
+        .unwrap_or_default();
+    let mut best = (DELIMITER_CANDIDATES[0], 0);
+
+    for candidate in DELIMITER_CANDIDATES {
+        let count = header.iter().filter(|&&byte| byte == candidate).count();
+
+        if count > best.1 {
+            best = (candidate, count);
+        }
+    }
 
-    Ok(records)
+    best.0
 }
 
+/// First field of the trailer row, see [`parse_trailer`].
+const TRAILER_MARKER: &str = "TOTAL";
+
+/// Finds the trailer row closing the export, holding the number of records
+/// and the sum of their `Sum` column, i.e. `TOTAL\t13\t7480.44`.
+///
+/// The trailer is not a record, so reading the export skips it.
+pub fn parse_trailer(contents: &str) -> Option<Trailer> {
+    let line = contents
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())?;
+    let delimiter = char::from(detect_delimiter(line.as_bytes()));
+    let mut fields = line.split(delimiter).map(str::trim);
+
+    if fields.next()? != TRAILER_MARKER {
+        return None;
+    }
+
+    Some(Trailer {
+        count: fields.next()?.parse().ok()?,
+        net: parse_exante_sum(fields.next()?).ok()?.signed(),
+    })
+}
+
+/// Imports an export held in memory, i.e. uploaded in a browser, and
+/// serializes its transactions to JSON.
+pub fn import_csv_str_to_json(contents: &str) -> Result<String, serde_json::Error> {
+    let records = read_csv_reader(contents.as_bytes()).collect::<Vec<_>>();
+    let (transactions, _) =
+        group_records_reporting_rejected(&records, &ImportOptions::default());
+
+    serde_json::to_string(&transactions)
+}
+
+/// Sorts records by their execution time, then by `uuid`, so that records
+/// sharing a timestamp are in the same order whatever their order in the
+/// file.
+pub fn sort_records<TRecord>(records: &mut [TRecord])
+where
+    TRecord: Borrow<RawRecord>,
+{
+    records.sort_by(|a, b| compare_records(a.borrow(), b.borrow()));
+}
+
+/// Order of [`sort_records`].
+fn compare_records(a: &RawRecord, b: &RawRecord) -> Ordering {
+    a.when.cmp(&b.when).then_with(|| a.uuid.cmp(&b.uuid))
+}
+
+/// Groups records executed at the same time into transactions, a
+/// transaction per account unless a transfer links them, see
+/// [`GroupingKey`].
+///
+/// Records don't have to be sorted, they're put in order with
+/// [`sort_records`] before grouping, so that records executed at the same
+/// time are always grouped the same. Records which cannot be converted into
+/// operations are left out, see [`group_records_reporting_rejected`] to find
+/// out which ones.
+///
+/// The output doesn't depend on the order of the records: operations are
+/// ordered by their ids within a transaction, and transactions by
+/// `started_at`, then by [fingerprint](Transaction::fingerprint).
 pub fn group_records_into_transactions(
     records: &[RawRecord],
 ) -> Result<Vec<Transaction>, RawRecordError> {
-    Ok(records
-        .linear_group_by(|a, b| a.when == b.when)
-        .filter_map(|group| {
-            let mut tx_builder = TransactionBuilder::default();
+    let (transactions, _) =
+        group_records_reporting_rejected(records, &ImportOptions::default());
+
+    Ok(transactions)
+}
+
+/// Same as [`group_records_into_transactions`], but also returns the records
+/// left out of the transactions.
+///
+/// A rejected record doesn't affect the other records of its group.
+pub fn group_records_reporting_rejected(
+    records: &[RawRecord],
+    options: &ImportOptions,
+) -> (Vec<Transaction>, Vec<RejectedRecord>) {
+    let mut records = records.iter().collect::<Vec<_>>();
+    let mut rejected = vec![];
+
+    sort_records(&mut records);
+
+    let mut transactions = records
+        .linear_group_by(|a, b| options.grouping.is_same_time(a, b))
+        .flat_map(|group| split_group(group, options))
+        .filter_map(|group| build_transaction(group.into_iter(), options, &mut rejected))
+        .collect::<Vec<_>>();
+
+    transactions.sort_by_cached_key(|tx| (tx.started_at(), tx.fingerprint()));
+
+    (transactions, rejected)
+}
+
+/// Same as [`group_records_reporting_rejected`], but leaves out the
+/// operations imported by a previous run, and returns the state to pass to
+/// the next one.
+///
+/// A transaction partially imported before keeps only its new operations.
+pub fn import_new_records(
+    records: &[RawRecord],
+    options: &ImportOptions,
+    state: &ImportState,
+) -> (Vec<Transaction>, Vec<RejectedRecord>, ImportState) {
+    let (transactions, rejected) = group_records_reporting_rejected(records, options);
+
+    let transactions = transactions
+        .into_iter()
+        .filter_map(|tx| {
+            tx.with_operations(
+                tx.iter()
+                    .filter(|operation| !state.contains(operation))
+                    .cloned()
+                    .collect(),
+            )
+            .ok()
+        })
+        .collect::<Vec<_>>();
+
+    let mut next_state = state.to_owned();
+
+    for operation in transactions.iter().flat_map(|tx| tx.iter()) {
+        next_state.record(operation);
+    }
+
+    (transactions, rejected, next_state)
+}
+
+/// Progress of repeated imports of a growing export, kept between runs
+/// (i.e. as JSON on disk).
+///
+/// Operations are told apart by their ids only, so records back-dated in a
+/// later export (i.e. corrections or late settlements) are still imported.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ImportState {
+    /// Ids of all the imported operations.
+    pub last_seen_operation_ids: HashSet<OperationId>,
+    /// Execution time of the latest imported operation.
+    pub last_timestamp: DateTime<Utc>,
+}
+
+impl ImportState {
+    /// Whether the operation was imported by a previous run.
+    pub fn contains(&self, operation: &Operation) -> bool {
+        self.last_seen_operation_ids.contains(&operation.id)
+    }
+
+    fn record(&mut self, operation: &Operation) {
+        self.last_timestamp = self.last_timestamp.max(operation.executed_at);
+        self.last_seen_operation_ids.insert(operation.id.to_owned());
+    }
+}
+
+/// Same as [`group_records_reporting_rejected`], but builds transactions on
+/// all cores.
+///
+/// Sorted records are split into batches at boundaries of the groups, so a
+/// group never spans two batches, and the output is the same as of the
+/// sequential grouping.
+#[cfg(feature = "rayon")]
+pub fn group_records_parallel(
+    records: &[RawRecord],
+    options: &ImportOptions,
+) -> (Vec<Transaction>, Vec<RejectedRecord>) {
+    use rayon::prelude::*;
 
-            for record in group {
-                tx_builder.add_operation(record.try_into().ok()?);
+    let mut records = records.iter().collect::<Vec<_>>();
+
+    records.par_sort_by(|a, b| compare_records(a, b));
+
+    let groups = records
+        .linear_group_by(|a, b| options.grouping.is_same_time(a, b))
+        .flat_map(|group| split_group(group, options))
+        .collect::<Vec<_>>();
+    let batch_size = groups.len().div_ceil(rayon::current_num_threads()).max(1);
+
+    let batches = groups
+        .par_chunks(batch_size)
+        .map(|groups| {
+            let mut rejected = vec![];
+            let transactions = groups
+                .iter()
+                .filter_map(|group| {
+                    build_transaction(group.iter().copied(), options, &mut rejected)
+                })
+                .collect::<Vec<_>>();
+
+            (transactions, rejected)
+        })
+        .collect::<Vec<_>>();
+
+    let mut transactions = vec![];
+    let mut rejected = vec![];
+
+    for (batch_transactions, batch_rejected) in batches {
+        transactions.extend(batch_transactions);
+        rejected.extend(batch_rejected);
+    }
+
+    transactions.sort_by_cached_key(|tx| (tx.started_at(), tx.fingerprint()));
+
+    (transactions, rejected)
+}
+
+/// Splits records executed at the same time by account (see
+/// [`GroupingKey::by_account`]), then groups larger than
+/// [`ImportOptions::max_group_size`] into groups of that size.
+fn split_group<'a>(
+    group: &[&'a RawRecord],
+    options: &ImportOptions,
+) -> Vec<Vec<&'a RawRecord>> {
+    let max_group_size = options.max_group_size.unwrap_or(usize::MAX).max(1);
+
+    split_by_account(group, options)
+        .into_iter()
+        .flat_map(|group| {
+            if group.len() > max_group_size {
+                log::warn!(
+                    "Splitting {} records executed at the same time into groups of {}",
+                    group.len(),
+                    max_group_size
+                );
             }
 
-            tx_builder.build().ok()
+            group
+                .chunks(max_group_size)
+                .map(<[_]>::to_vec)
+                .collect::<Vec<_>>()
         })
-        .collect::<Vec<_>>())
+        .collect()
+}
+
+/// Splits records into groups of accounts linked by transfers between them,
+/// ordered by their first record.
+fn split_by_account<'a>(
+    group: &[&'a RawRecord],
+    options: &ImportOptions,
+) -> Vec<Vec<&'a RawRecord>> {
+    if !options.grouping.by_account {
+        return vec![group.to_vec()];
+    }
+
+    let ledger = |record: &RawRecord| {
+        Ledger::canonical_with(&record.account_id, options.ledger_case_folding)
+    };
+    let mut linked_accounts: Vec<BTreeSet<Ledger>> = vec![];
+
+    for record in group {
+        let mut accounts = BTreeSet::from([ledger(record)]);
+        accounts.extend(record.counterparty(options));
+
+        let (linked, unlinked): (Vec<_>, Vec<_>) = linked_accounts
+            .into_iter()
+            .partition(|linked| !linked.is_disjoint(&accounts));
+
+        linked_accounts = unlinked;
+        linked_accounts.push(linked.into_iter().flatten().chain(accounts).collect());
+    }
+
+    let mut groups: Vec<(usize, Vec<&RawRecord>)> = vec![];
+
+    for record in group {
+        let account = ledger(record);
+        let i = linked_accounts
+            .iter()
+            .position(|linked| linked.contains(&account))
+            .unwrap();
+
+        match groups.iter_mut().find(|(j, _)| *j == i) {
+            Some((_, group)) => group.push(record),
+            None => groups.push((i, vec![record])),
+        }
+    }
+
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Record which couldn't be converted into an operation.
+#[derive(Debug)]
+pub struct RejectedRecord {
+    pub uuid: String,
+    pub error: RawRecordError,
+}
+
+/// Groups records into transactions as they come and passes each
+/// transaction to the sink, without keeping all records in memory.
+///
+/// Unlike [`group_records_into_transactions`], records are expected to be in
+/// chronological order already, as only consecutive records executed at the
+/// same time are grouped together. The sink is not finished, so more
+/// records can be streamed into it.
+///
+/// The optional `progress` callback is called each time a transaction is
+/// passed to the sink.
+pub fn stream_records_into_sink<TRecords, TSink>(
+    records: TRecords,
+    sink: &mut TSink,
+    options: &ImportOptions,
+    mut progress: Option<&mut dyn FnMut(ImportProgress)>,
+) -> Result<(), SinkError>
+where
+    TRecords: IntoIterator<Item = RawRecord>,
+    TSink: TransactionSink,
+{
+    let mut group: Vec<RawRecord> = vec![];
+    let mut import_progress = ImportProgress::default();
+
+    let mut emit = |group: &[RawRecord], import_progress: &mut ImportProgress| {
+        for group in split_group(&group.iter().collect::<Vec<_>>(), options) {
+            let mut rejected = vec![];
+            let tx = build_transaction(group.into_iter(), options, &mut rejected);
+
+            import_progress.records_rejected += rejected.len();
+
+            if let Some(tx) = tx {
+                sink.accept(tx)?;
+
+                import_progress.transactions_emitted += 1;
+
+                if let Some(progress) = progress.as_mut() {
+                    progress(*import_progress);
+                }
+            }
+        }
+
+        Ok(())
+    };
+
+    for record in records {
+        if group
+            .last()
+            .is_some_and(|last| !options.grouping.is_same_time(last, &record))
+        {
+            emit(&group, &mut import_progress)?;
+
+            group.clear();
+        }
+
+        import_progress.records_read += 1;
+        group.push(record);
+    }
+
+    emit(&group, &mut import_progress)
+}
+
+/// Snapshot of a running import.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ImportProgress {
+    pub records_read: usize,
+    /// Records which couldn't be converted into operations, so weren't
+    /// included in any transaction.
+    pub records_rejected: usize,
+    pub transactions_emitted: usize,
+}
+
+/// Builds a transaction from the records which can be converted into
+/// operations, pushing the others to `rejected`, unless they're skipped.
+fn build_transaction<'a>(
+    group: impl Iterator<Item = &'a RawRecord>,
+    options: &ImportOptions,
+    rejected: &mut Vec<RejectedRecord>,
+) -> Option<Transaction> {
+    let mut operations = vec![];
+
+    for record in group {
+        match record.to_operation(options) {
+            Ok(operation) => operations.push(operation),
+            Err(RawRecordError::SkippedAsset(_)) => {}
+            Err(error) => rejected.push(RejectedRecord {
+                uuid: record.uuid.to_owned(),
+                error,
+            }),
+        }
+    }
+
+    let is_trade_leg = |operation: &Operation| {
+        operation.source_type.as_deref() == Some(TRADE_OPERATION_TYPE)
+    };
+    let has_asset_leg = operations
+        .iter()
+        .any(|op| is_trade_leg(op) && op.asset.class() != AssetClass::Fiat);
+    let mut tx_builder = TransactionBuilder::default();
+
+    for mut operation in operations {
+        // cash of a trade which asset leg is missing from the export
+        if !has_asset_leg && is_trade_leg(&operation) {
+            operation.kind = match operation.kind {
+                OperationKind::Inflow(_) => OperationKind::Inflow(InflowOperation::Sale),
+                _ => OperationKind::Outflow(OutflowOperation::Purchase),
+            };
+        }
+
+        tx_builder.add_operation(operation);
+    }
+
+    tx_builder.build().ok()
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct RawRecord {
-    #[serde(rename = "Transaction ID")]
-    tx_id: String,
+    #[allow(dead_code)]
+    #[serde(rename = "Transaction ID", alias = "Transaction Id")]
+    pub(super) tx_id: String,
 
-    #[serde(rename = "Account ID")]
-    account_id: String,
+    #[serde(rename = "Account ID", alias = "Account Id")]
+    pub(super) account_id: String,
 
-    #[serde(rename = "Symbol ID")]
-    symbol_id: String,
+    #[serde(rename = "Symbol ID", alias = "Symbol Id")]
+    pub(super) symbol_id: String,
 
-    #[serde(rename = "ISIN")]
-    isin: String,
+    #[serde(rename = "ISIN", alias = "Isin")]
+    pub(super) isin: String,
 
-    #[serde(rename = "Operation type")]
-    operation_type: String,
+    #[serde(rename = "Operation type", alias = "Operation Type")]
+    pub(super) operation_type: String,
 
     #[serde(rename = "When", deserialize_with = "deserialize_exante_date")]
-    when: chrono::DateTime<chrono::Utc>,
+    pub(super) when: chrono::DateTime<chrono::Utc>,
 
-    #[serde(rename = "Sum")]
-    sum: f32,
+    /// Parsed from the text, so that all of its decimal places are kept, up
+    /// to the 28 a [`Decimal`] holds, i.e. satoshis of `0.00000001` BTC.
+    #[serde(rename = "Sum", deserialize_with = "deserialize_exante_sum")]
+    pub(super) sum: ExanteSum,
 
     #[serde(rename = "Asset")]
-    asset: String,
+    pub(super) asset: String,
+
+    #[serde(rename = "Comment", default)]
+    pub(super) comment: Option<String>,
 
-    #[serde(rename = "UUID")]
-    uuid: String,
+    #[serde(rename = "UUID", alias = "Uuid")]
+    pub(super) uuid: String,
 }
 
 #[derive(Error, Debug)]
@@ -87,43 +551,410 @@ pub enum RawRecordError {
     #[error("{0}")]
     OperationId(#[from] OperationIdError),
 
+    #[allow(clippy::upper_case_acronyms)]
     #[error("{0}")]
     ISIN(#[from] ISINError),
 
-    #[error("Invalid record value")]
-    Value(#[from] rust_decimal::Error),
+    #[error("Missing record UUID")]
+    MissingId,
+
+    /// Record with a blank `Account ID`, which would make a nameless ledger.
+    #[error("Missing account id")]
+    MissingAccount,
+
+    #[error("Unknown asset: {0}")]
+    UnknownAsset(String),
+
+    /// Record in an unknown asset, left out of the import as set by
+    /// [`ImportOptions::on_unknown_asset`] rather than rejected.
+    #[error("Skipped record in unknown asset: {0}")]
+    SkippedAsset(String),
+
+    /// Record executed after the import, rejected as set by
+    /// [`ImportOptions::reject_future`].
+    #[error("Record executed in the future: {0}")]
+    FutureTimestamp(DateTime<Utc>),
+}
+
+/// Finds the ISIN of a security by its Exante symbol, i.e. `AAPL.NASDAQ`.
+pub trait SymbolResolver {
+    fn resolve_isin(&self, symbol_id: &str) -> Option<ISIN>;
+}
+
+impl SymbolResolver for HashMap<String, ISIN> {
+    fn resolve_isin(&self, symbol_id: &str) -> Option<ISIN> {
+        self.get(symbol_id).cloned()
+    }
+}
+
+/// Record whose ISIN disagrees with the one resolved for its symbol.
+#[derive(Debug, PartialEq)]
+pub struct SymbolMismatch {
+    pub uuid: String,
+    pub symbol_id: String,
+    pub isin: String,
+    pub resolved_isin: ISIN,
+}
+
+impl RawRecord {
+    /// Compares the record's ISIN with the one resolved for its symbol.
+    ///
+    /// Records without an ISIN (i.e. cash legs) and symbols unknown to the
+    /// resolver are not checked.
+    pub fn check_symbol(&self, resolver: &dyn SymbolResolver) -> Option<SymbolMismatch> {
+        if self.isin == "None" {
+            return None;
+        }
+
+        let resolved_isin = resolver.resolve_isin(&self.symbol_id)?;
+
+        let matches = self
+            .isin
+            .parse::<ISIN>()
+            .is_ok_and(|isin| isin == resolved_isin);
+
+        (!matches).then(|| SymbolMismatch {
+            uuid: self.uuid.to_owned(),
+            symbol_id: self.symbol_id.to_owned(),
+            isin: self.isin.to_owned(),
+            resolved_isin,
+        })
+    }
+}
+
+/// Collects records whose `Symbol ID` and `ISIN` columns disagree, which
+/// points at a corrupt export.
+pub fn check_symbols(
+    records: &[RawRecord],
+    resolver: &dyn SymbolResolver,
+) -> Vec<SymbolMismatch> {
+    records
+        .iter()
+        .filter_map(|record| record.check_symbol(resolver))
+        .collect()
+}
+
+/// Operation whose value was rounded to the scale of its asset on import.
+#[derive(Debug, PartialEq)]
+pub struct PrecisionWarning {
+    pub operation_id: OperationId,
+    /// Source value less the stored one.
+    pub lost: Decimal,
+}
+
+impl RawRecord {
+    /// Compares the record's sum with the value of the operation it makes,
+    /// which differ when [`ImportOptions::round_to_asset_scale`] is set.
+    ///
+    /// Records which don't make an operation are not checked.
+    pub fn check_precision(&self, options: &ImportOptions) -> Option<PrecisionWarning> {
+        let operation = self.to_operation(options).ok()?;
+        let lost = self.sum.magnitude - operation.value;
+
+        (!lost.is_zero()).then_some(PrecisionWarning {
+            operation_id: operation.id,
+            lost,
+        })
+    }
+}
+
+/// Collects records whose sum had more decimal places than their asset is
+/// kept at, and lost them on import.
+pub fn check_precision(
+    records: &[RawRecord],
+    options: &ImportOptions,
+) -> Vec<PrecisionWarning> {
+    records
+        .iter()
+        .filter_map(|record| record.check_precision(options))
+        .collect()
+}
+
+/// Settings of an import.
+#[derive(Clone, Debug, Default)]
+pub struct ImportOptions {
+    /// Whether records with a blank `UUID` get an id derived from their time,
+    /// sum and asset, instead of being rejected. Records equal in all of
+    /// these get the same id, sums being compared as exact decimals, i.e.
+    /// `-5.0` equal to `-5`.
+    pub synthesize_ids: bool,
+    /// Largest number of records grouped into one transaction. Records
+    /// executed at the same time beyond the limit make further transactions,
+    /// which guards against exports with many records sharing a timestamp.
+    pub max_group_size: Option<usize>,
+    /// Whether account ids differing in case only make distinct ledgers.
+    pub ledger_case_folding: CaseFolding,
+    /// Kinds of operations by the record's type and comment.
+    pub classification: ClassificationRules,
+    /// What becomes of records which are neither in a security nor in a
+    /// supported currency.
+    pub on_unknown_asset: UnknownAssetPolicy,
+    /// Whether values are rounded to the scale of their asset (see
+    /// [`AssetId::round`]). Records losing precision this way are reported by
+    /// [`check_precision`].
+    pub round_to_asset_scale: bool,
+    /// Which records make a transaction together.
+    pub grouping: GroupingKey,
+    /// Whether records executed after `as_of` are rejected, as they're
+    /// likely corrupt and would stretch the dates of the import.
+    pub reject_future: bool,
+    /// Time of the import records are checked against when
+    /// `reject_future` is set, now when `None`.
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+/// Key of the records making a transaction together: the time they're
+/// executed at and, optionally, their account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupingKey {
+    /// Largest gap between consecutive records of a transaction, zero to
+    /// only group records executed at the same time.
+    pub window: chrono::Duration,
+    /// Whether records of different accounts make different transactions,
+    /// unless a transfer between the accounts links them.
+    pub by_account: bool,
+}
+
+impl Default for GroupingKey {
+    fn default() -> Self {
+        Self {
+            window: chrono::Duration::zero(),
+            by_account: true,
+        }
+    }
+}
+
+impl GroupingKey {
+    /// Whether the records, `later` executed not before `earlier`, are close
+    /// enough in time to make a transaction.
+    fn is_same_time(&self, earlier: &RawRecord, later: &RawRecord) -> bool {
+        later.when - earlier.when <= self.window
+    }
+}
+
+/// Handling of records in an asset which can't be identified, i.e. a
+/// currency not supported yet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum UnknownAssetPolicy {
+    /// Rejects the record, see [`RejectedRecord`].
+    #[default]
+    FailRow,
+    /// Leaves the record out of the import without reporting it.
+    Skip,
+    /// Imports the record as an operation in the given asset.
+    Placeholder(AssetId),
+}
+
+/// Classifies records meeting all the configured criteria as operations of
+/// the given kind.
+///
+/// # Example
+/// ```
+/// use delfin::{
+///     data_sources::exante::{ClassificationRule, ClassificationRules},
+///     operation::{InflowOperation, OperationKind},
+/// };
+///
+/// let mut rules = ClassificationRules::default();
+/// rules.rule(
+///     ClassificationRule::new(OperationKind::Inflow(InflowOperation::Income))
+///         .comment_contains("salary")
+///         .inflow(),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClassificationRule {
+    kind: OperationKind,
+    operation_type: Option<String>,
+    comment_contains: Option<String>,
+    inflow: Option<bool>,
+}
+
+impl ClassificationRule {
+    pub fn new(kind: OperationKind) -> Self {
+        Self {
+            kind,
+            operation_type: None,
+            comment_contains: None,
+            inflow: None,
+        }
+    }
+
+    /// Records of the type, i.e. `DIVIDEND`.
+    pub fn operation_type(mut self, operation_type: &str) -> Self {
+        self.operation_type = Some(operation_type.to_owned());
+
+        self
+    }
+
+    /// Records with a comment containing the text, ignoring case.
+    pub fn comment_contains(mut self, text: &str) -> Self {
+        self.comment_contains = Some(text.to_lowercase());
+
+        self
+    }
+
+    /// Records credited, see [`ExanteSum::is_credit`].
+    pub fn inflow(mut self) -> Self {
+        self.inflow = Some(true);
+
+        self
+    }
+
+    /// Records debited, see [`ExanteSum::is_credit`].
+    pub fn outflow(mut self) -> Self {
+        self.inflow = Some(false);
+
+        self
+    }
+
+    pub fn matches(&self, record: &RawRecord) -> bool {
+        self.operation_type
+            .as_ref()
+            .is_none_or(|operation_type| operation_type == &record.operation_type)
+            && self.comment_contains.as_ref().is_none_or(|text| {
+                record
+                    .comment
+                    .as_ref()
+                    .is_some_and(|comment| comment.to_lowercase().contains(text))
+            })
+            && self
+                .inflow
+                .is_none_or(|inflow| inflow == record.sum.is_credit)
+    }
+}
+
+/// Rules classifying records, see [`ClassificationRule`].
+///
+/// Rules added later take precedence over earlier ones. Records no rule
+/// matches are classified by their operation type and whether they're
+/// credited, see [`operation_kind_from_direction`], which covers the types of
+/// the Exante export.
+#[derive(Clone, Debug, Default)]
+pub struct ClassificationRules {
+    rules: Vec<ClassificationRule>,
+}
+
+impl ClassificationRules {
+    /// No rules at all, so that every record is classified by its type.
+    pub fn empty() -> Self {
+        Self { rules: vec![] }
+    }
+
+    pub fn rule(&mut self, rule: ClassificationRule) -> &mut Self {
+        self.rules.push(rule);
+
+        self
+    }
+
+    pub fn classify(&self, record: &RawRecord) -> OperationKind {
+        match self.rules.iter().rev().find(|rule| rule.matches(record)) {
+            Some(rule) => rule.kind.to_owned(),
+            None => operation_kind_from_direction(
+                record.sum.is_credit,
+                &record.operation_type,
+            ),
+        }
+    }
 }
 
-impl<'a> TryInto<Operation> for &'a RawRecord {
+impl TryFrom<&RawRecord> for Operation {
     type Error = RawRecordError;
 
-    fn try_into(self) -> Result<Operation, Self::Error> {
-        // TODO: assign exact operation kind
-        let kind = if self.sum > 0.0 {
-            OperationKind::Inflow(InflowOperation::Deposit)
-        } else {
-            OperationKind::Outflow(OutflowOperation::Withdrawal)
-        };
+    fn try_from(record: &RawRecord) -> Result<Operation, Self::Error> {
+        record.to_operation(&ImportOptions::default())
+    }
+}
+
+impl RawRecord {
+    /// Account on the other side of a transfer, named at the end of the
+    /// comment.
+    fn counterparty(&self, options: &ImportOptions) -> Option<Ledger> {
+        if self.operation_type != TRANSFER_OPERATION_TYPE {
+            return None;
+        }
+
+        let account_id = self.comment.as_deref()?.split_whitespace().last()?;
+
+        let ledger =
+            |name: &str| Ledger::canonical_with(name, options.ledger_case_folding);
+
+        Some(ledger(account_id))
+            .filter(|counterparty| counterparty != &ledger(&self.account_id))
+    }
+
+    pub fn to_operation(
+        &self,
+        options: &ImportOptions,
+    ) -> Result<Operation, RawRecordError> {
+        if options.reject_future && self.when > options.as_of.unwrap_or_else(Utc::now) {
+            return Err(RawRecordError::FutureTimestamp(self.when));
+        }
+
+        if self.account_id.trim().is_empty() {
+            return Err(RawRecordError::MissingAccount);
+        }
+
+        let kind = options.classification.classify(self);
 
         let asset_id = if &self.isin != "None" {
             AssetId::Security(self.isin.parse::<ISIN>()?)
+        } else if let Ok(currency) = self.asset.parse::<FiatCurrency>() {
+            AssetId::Currency(currency)
+        } else {
+            match &options.on_unknown_asset {
+                UnknownAssetPolicy::FailRow => {
+                    return Err(RawRecordError::UnknownAsset(self.asset.to_owned()))
+                }
+                UnknownAssetPolicy::Skip => {
+                    return Err(RawRecordError::SkippedAsset(self.asset.to_owned()))
+                }
+                UnknownAssetPolicy::Placeholder(asset_id) => asset_id.to_owned(),
+            }
+        };
+
+        let id = if !self.uuid.trim().is_empty() {
+            self.uuid.parse::<OperationId>()?
+        } else if options.synthesize_ids {
+            let fingerprint = Fingerprint::of(
+                format!(
+                    "{}|{}|{}",
+                    self.when.timestamp(),
+                    self.sum.signed().normalize(),
+                    self.asset
+                )
+                .as_bytes(),
+            );
+
+            format!("SYN-{}", fingerprint).parse::<OperationId>()?
         } else {
-            // TODO: map the currency
-            AssetId::Currency(FiatCurrency::USD)
+            return Err(RawRecordError::MissingId);
         };
 
         Ok(Operation {
-            id: self.uuid.parse::<OperationId>()?,
+            id,
             kind,
-            ledger: Ledger::new(self.account_id.as_str()),
+            ledger: Ledger::canonical_with(&self.account_id, options.ledger_case_folding),
+            value: if options.round_to_asset_scale {
+                asset_id.round(self.sum.magnitude)
+            } else {
+                self.sum.magnitude
+            },
             asset: Asset::new(asset_id, self.asset.to_owned()),
-            value: self.sum.abs().try_into()?,
             executed_at: self.when,
+            description: self
+                .comment
+                .as_ref()
+                .filter(|comment| comment.as_str() != "None")
+                .cloned(),
+            source_type: Some(self.operation_type.to_owned()),
+            fx_rate: None,
+            counterparty: self.counterparty(options),
         })
     }
 }
 
-const EXANTE_DATE_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+const EXANTE_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
 
 // The signature of a deserialize_with function must follow the pattern:
 //
@@ -137,15 +968,109 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Utc.datetime_from_str(&s, EXANTE_DATE_FORMAT)
-        .map_err(serde::de::Error::custom)
+
+    parse_exante_date(&s).map_err(serde::de::Error::custom)
+}
+
+/// Deserializes a sum written as text, see [`parse_exante_sum`].
+pub fn deserialize_exante_sum<'de, D>(deserializer: D) -> Result<ExanteSum, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    parse_exante_sum(&s).map_err(serde::de::Error::custom)
+}
+
+/// Sum of a record, with the sign kept apart from the magnitude, so that
+/// `-0.00` and `0.00` stay distinct.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ExanteSum {
+    /// Whether the sum is credited to the account, i.e. isn't written with a
+    /// leading `-`.
+    pub is_credit: bool,
+    pub magnitude: Decimal,
+}
+
+impl ExanteSum {
+    /// Sum as a signed amount, negative when debited.
+    pub fn signed(&self) -> Decimal {
+        if self.is_credit {
+            self.magnitude
+        } else {
+            -self.magnitude
+        }
+    }
+}
+
+impl From<Decimal> for ExanteSum {
+    fn from(amount: Decimal) -> Self {
+        ExanteSum {
+            is_credit: amount.is_sign_positive(),
+            magnitude: amount.abs(),
+        }
+    }
+}
+
+/// Parses a sum from the Exante export, either in the plain (i.e. `-1300.5`)
+/// or the scientific notation (i.e. `1E-8`).
+///
+/// Sums with more decimal places than a [`Decimal`] holds are rejected
+/// rather than rounded, as are sums with more than a single leading `-` or
+/// with a `+` (i.e. `--5`).
+pub fn parse_exante_sum(s: &str) -> Result<ExanteSum, rust_decimal::Error> {
+    let s = s.trim();
+    let magnitude = s.strip_prefix('-').unwrap_or(s);
+
+    if magnitude.starts_with(['-', '+']) {
+        return Err(rust_decimal::Error::from(format!(
+            "Invalid sign of sum: {}",
+            s
+        )));
+    }
+
+    Ok(ExanteSum {
+        is_credit: !s.starts_with('-'),
+        magnitude: Decimal::from_str_exact(magnitude)
+            .or_else(|_| Decimal::from_scientific(magnitude))?,
+    })
+}
+
+/// Parses a date from the Exante export.
+///
+/// The `%Y-%m-%d %H:%M:%S` format (optionally with fractional seconds) is
+/// tried first, then RFC 3339 (i.e. `2021-01-01T10:00:00Z`) as a fallback.
+pub fn parse_exante_date(s: &str) -> Result<DateTime<Utc>, ExanteDateError> {
+    if let Ok(date) = NaiveDateTime::parse_from_str(s, EXANTE_DATE_FORMAT) {
+        return Ok(date.and_utc());
+    }
+
+    DateTime::parse_from_rfc3339(s)
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(|_| ExanteDateError::Unsupported(s.to_owned()))
+}
+
+#[derive(Debug, Error)]
+pub enum ExanteDateError {
+    #[error("Unsupported date format: {0}")]
+    Unsupported(String),
 }
 
 #[cfg(test)]
 mod tests {
-    use claim::{assert_gt, assert_ok};
+    use std::fs;
+
+    use chrono::TimeZone;
+    use claim::{assert_err, assert_gt, assert_matches, assert_ok};
+    use rust_decimal_macros::dec;
 
     use super::*;
+    use crate::{
+        asset::TokenId,
+        data_sources::reconcile,
+        operation::{AdjustmentOperation, FeeCategory},
+        sink::VecSink,
+    };
 
     static DEMO_CSV_FILE_PATH: &str = "input/exante/demo.csv";
 
@@ -160,9 +1085,732 @@ mod tests {
         assert_gt!(operations.len(), 0);
     }
 
+    #[test]
+    fn detect_tab_and_comma_delimiters() {
+        let tab_file = fs::read(DEMO_CSV_FILE_PATH).unwrap();
+        let comma_file = String::from_utf8(tab_file.to_owned())
+            .unwrap()
+            .replace('\t', ",");
+
+        assert_eq!(detect_delimiter(&tab_file), b'\t');
+        assert_eq!(detect_delimiter(comma_file.as_bytes()), b',');
+        assert_eq!(
+            read_csv_reader(comma_file.as_bytes()).count(),
+            read_csv_reader(tab_file.as_slice()).count()
+        );
+    }
+
+    #[test]
+    fn parse_exante_date_formats() {
+        let expected = Utc.with_ymd_and_hms(2021, 1, 1, 10, 0, 0).unwrap();
+
+        assert_eq!(parse_exante_date("2021-01-01 10:00:00").unwrap(), expected);
+        assert_eq!(parse_exante_date("2021-01-01T10:00:00Z").unwrap(), expected);
+        assert_eq!(
+            parse_exante_date("2021-01-01 10:00:00.123").unwrap(),
+            expected + chrono::Duration::milliseconds(123)
+        );
+    }
+
+    #[test]
+    fn satoshi_survives_import() {
+        let contents = "Transaction ID\tAccount ID\tSymbol ID\tISIN\tOperation type\tWhen\tSum\tAsset\tEUR equivalent\tComment\tUUID\tParent UUID\n\
+            1\tABC1234.001\tNone\tNone\tFUNDING/WITHDRAWAL\t2021-01-01 10:00:00\t0.00000001\tBTC\t0.00\tNone\t1\tNone\n";
+
+        let records = read_csv_reader(contents.as_bytes()).collect::<Vec<_>>();
+        let (transactions, _) =
+            group_records_reporting_rejected(&records, &ImportOptions {
+                on_unknown_asset: UnknownAssetPolicy::Placeholder(AssetId::Token(
+                    TokenId("BTC".into()),
+                )),
+                ..Default::default()
+            });
+
+        assert_eq!(transactions[0].operations()[0].value, dec!(0.00000001));
+        assert_eq!(parse_exante_sum("1E-8"), Ok(dec!(0.00000001).into()));
+        assert_err!(parse_exante_sum("0.00000000000000000000000000001"));
+    }
+
+    #[test]
+    fn sign_of_zero_sum_is_kept() {
+        let debited = parse_exante_sum("-0.00").unwrap();
+        let credited = parse_exante_sum("0.00").unwrap();
+
+        assert!(!debited.is_credit);
+        assert!(credited.is_credit);
+        assert_eq!(debited.magnitude, credited.magnitude);
+
+        let kind = |sum| {
+            RawRecord {
+                operation_type: "FUNDING/WITHDRAWAL".into(),
+                sum,
+                ..record("1", "2021-01-01 10:00:00", dec!(0))
+            }
+            .to_operation(&ImportOptions::default())
+            .unwrap()
+            .kind
+        };
+
+        assert_eq!(
+            kind(debited),
+            OperationKind::Outflow(OutflowOperation::Withdrawal)
+        );
+        assert_eq!(
+            kind(credited),
+            OperationKind::Inflow(InflowOperation::Deposit)
+        );
+    }
+
+    #[test]
+    fn sum_with_more_than_one_sign_is_rejected() {
+        assert_eq!(parse_exante_sum("-5").unwrap().magnitude, dec!(5));
+        assert_err!(parse_exante_sum("--5"));
+        assert_err!(parse_exante_sum("-+5"));
+        assert_err!(parse_exante_sum("+5"));
+        assert_err!(parse_exante_sum("--1E-8"));
+    }
+
+    #[test]
+    fn parse_exante_date_rejects_unknown_format() {
+        assert_err!(parse_exante_date("01/01/2021 10:00"));
+    }
+
+    fn record(uuid: &str, when: &str, sum: Decimal) -> RawRecord {
+        RawRecord {
+            tx_id: uuid.to_owned(),
+            account_id: "ABC1234.001".into(),
+            symbol_id: "None".into(),
+            isin: "None".into(),
+            operation_type: "TRADE".into(),
+            when: parse_exante_date(when).unwrap(),
+            sum: sum.into(),
+            asset: "USD".into(),
+            comment: None,
+            uuid: uuid.to_owned(),
+        }
+    }
+
+    #[test]
+    fn group_unsorted_records() {
+        let records = vec![
+            record("1", "2021-01-02 10:00:00", dec!(100)),
+            record("2", "2021-01-01 10:00:00", dec!(-5)),
+            record("3", "2021-01-02 10:00:00", dec!(-100)),
+            record("4", "2021-01-03 10:00:00", dec!(1)),
+            record("5", "2021-01-02 10:00:00", dec!(-2)),
+        ];
+
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        assert_eq!(transactions.len(), 3);
+
+        let ids = transactions[1]
+            .operations()
+            .iter()
+            .map(|op| op.id.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, vec!["1", "3", "5"]);
+    }
+
+    #[test]
+    fn records_sharing_a_timestamp_are_sorted_by_uuid() {
+        let mut records = vec![
+            record("c", "2021-01-02 10:00:00", dec!(1)),
+            record("b", "2021-01-02 10:00:00", dec!(1)),
+            record("d", "2021-01-01 10:00:00", dec!(1)),
+            record("a", "2021-01-02 10:00:00", dec!(1)),
+        ];
+
+        sort_records(&mut records);
+
+        let uuids = records.iter().map(|record| record.uuid.as_str());
+        assert!(uuids.eq(["d", "a", "b", "c"]));
+    }
+
+    #[test]
+    fn output_does_not_depend_on_record_order() {
+        let mut records = read_csv_file(Path::new(DEMO_CSV_FILE_PATH)).unwrap();
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        records.reverse();
+
+        assert_eq!(
+            group_records_into_transactions(&records).unwrap(),
+            transactions
+        );
+    }
+
+    #[test]
+    fn comment_is_kept_as_operation_description() {
+        let records = read_csv_file(Path::new(DEMO_CSV_FILE_PATH))
+            .expect("Could not load the CSV file");
+
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        let descriptions = transactions
+            .iter()
+            .flat_map(|tx| tx.operations())
+            .filter_map(|op| op.description.as_deref())
+            .collect::<Vec<_>>();
+
+        assert!(descriptions.contains(&"Deposit from bank account"));
+        assert!(!descriptions.contains(&"None"));
+    }
+
+    #[test]
+    fn mismatched_symbol_is_reported() {
+        let resolver = HashMap::from([(
+            "AAPL.NASDAQ".to_owned(),
+            "US0378331005".parse::<ISIN>().unwrap(),
+        )]);
+
+        let mut matching = record("1", "2021-01-01 10:00:00", dec!(10));
+        matching.symbol_id = "AAPL.NASDAQ".into();
+        matching.isin = "US0378331005".into();
+
+        let mut mismatched = record("2", "2021-01-01 10:00:00", dec!(10));
+        mismatched.symbol_id = "AAPL.NASDAQ".into();
+        mismatched.isin = "US5949181045".into();
+
+        let mut cash = record("3", "2021-01-01 10:00:00", dec!(-1500));
+        cash.symbol_id = "AAPL.NASDAQ".into();
+
+        let warnings = check_symbols(&[matching, mismatched, cash], &resolver);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].uuid, "2");
+        assert_eq!(warnings[0].resolved_isin.as_str(), "US0378331005");
+    }
+
+    #[test]
+    fn streaming_into_sink_matches_eager_grouping() {
+        let file = fs::File::open(DEMO_CSV_FILE_PATH).unwrap();
+        let mut sink = VecSink::default();
+
+        stream_records_into_sink(
+            read_csv_reader(file),
+            &mut sink,
+            &ImportOptions::default(),
+            None,
+        )
+        .unwrap();
+
+        let records = read_csv_file(Path::new(DEMO_CSV_FILE_PATH)).unwrap();
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        assert_eq!(sink.transactions(), transactions.as_slice());
+    }
+
+    #[test]
+    fn alternate_header_names_are_recognized() {
+        let records = read_csv_file(Path::new("input/exante/alternate_headers.csv"));
+
+        assert_eq!(records.unwrap().len(), 13);
+    }
+
+    #[test]
+    fn import_is_reconciled_against_trailer() {
+        let reconcile_file = |path| {
+            let contents = fs::read_to_string(path).unwrap();
+            let records = read_csv_reader(contents.as_bytes()).collect::<Vec<_>>();
+            let transactions = group_records_into_transactions(&records).unwrap();
+
+            reconcile(&transactions, &parse_trailer(&contents).unwrap()).unwrap()
+        };
+
+        let report = reconcile_file("input/exante/trailer.csv");
+
+        assert!(report.matches);
+        assert_eq!(report.actual_count, 13);
+        assert_eq!(report.actual_net, dec!(7480.44));
+
+        let report = reconcile_file("input/exante/trailer_mismatch.csv");
+
+        assert!(!report.matches);
+        assert_eq!(report.expected_count, 14);
+        assert_eq!(report.actual_count, 13);
+        assert_eq!(parse_trailer("a\tb\n"), None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn load_gzipped_file_contents() {
+        use std::io::Write;
+
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(&fs::read(DEMO_CSV_FILE_PATH).unwrap())
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let gzipped_file_path = dir.path().join("demo.csv.gz");
+        fs::write(&gzipped_file_path, encoder.finish().unwrap()).unwrap();
+
+        let gzipped_records = read_csv_file(&gzipped_file_path).unwrap();
+        let records = read_csv_file(Path::new(DEMO_CSV_FILE_PATH)).unwrap();
+
+        assert_gt!(gzipped_records.len(), 0);
+        assert_eq!(
+            group_records_into_transactions(&gzipped_records).unwrap(),
+            group_records_into_transactions(&records).unwrap()
+        );
+    }
+
+    #[test]
+    fn commission_is_a_fee() {
+        let mut commission = record("1", "2021-01-01 10:00:00", dec!(-2));
+        commission.operation_type = "COMMISSION".into();
+
+        let operation = Operation::try_from(&commission).unwrap();
+
+        assert_eq!(
+            operation.kind,
+            OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            })
+        );
+        assert_eq!(operation.value, rust_decimal_macros::dec!(2));
+        assert_eq!(operation.source_type.as_deref(), Some("COMMISSION"));
+    }
+
+    #[test]
+    fn negative_commission_is_a_rebate() {
+        let mut rebate = record("1", "2021-01-01 10:00:00", dec!(0.75));
+        rebate.operation_type = "COMMISSION".into();
+
+        let operation = Operation::try_from(&rebate).unwrap();
+
+        assert_eq!(
+            operation.kind,
+            OperationKind::Inflow(InflowOperation::Rebate)
+        );
+        assert_eq!(operation.value, dec!(0.75));
+    }
+
+    #[test]
+    fn trade_without_asset_leg_is_a_purchase_or_sale() {
+        let records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(-1300.5)),
+            record("2", "2021-01-02 10:00:00", dec!(487.4)),
+        ];
+
+        let transactions = group_records_into_transactions(&records).unwrap();
+
+        assert_eq!(
+            transactions[0].operations()[0].kind,
+            OperationKind::Outflow(OutflowOperation::Purchase)
+        );
+        assert_eq!(
+            transactions[1].operations()[0].kind,
+            OperationKind::Inflow(InflowOperation::Sale)
+        );
+
+        // the cash leg of a complete trade is left as it was
+        let transactions =
+            group_records_into_transactions(&read_csv_file(DEMO_CSV_FILE_PATH).unwrap())
+                .unwrap();
+
+        assert!(transactions
+            .iter()
+            .flat_map(|tx| tx.iter())
+            .all(|op| !matches!(
+                op.kind,
+                OperationKind::Inflow(InflowOperation::Sale)
+                    | OperationKind::Outflow(OutflowOperation::Purchase)
+            )));
+    }
+
+    #[test]
+    fn transfer_between_accounts_is_paired() {
+        let mut outgoing = record("1", "2021-01-01 10:00:00", dec!(-500));
+        outgoing.operation_type = "TRANSFER".into();
+        outgoing.comment = Some("Transfer to ABC1234.002".into());
+
+        let mut incoming = record("2", "2021-01-01 10:00:00", dec!(500));
+        incoming.operation_type = "TRANSFER".into();
+        incoming.account_id = "ABC1234.002".into();
+        incoming.comment = Some("Transfer from ABC1234.001".into());
+
+        let outgoing = Operation::try_from(&outgoing).unwrap();
+        let incoming = Operation::try_from(&incoming).unwrap();
+
+        assert_eq!(outgoing.counterparty, Some(Ledger::new("ABC1234.002")));
+        assert!(outgoing.is_transfer_counterpart(&incoming));
+    }
+
+    #[test]
+    fn records_of_different_accounts_make_different_transactions() {
+        let mut records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(100)),
+            record("2", "2021-01-01 10:00:00", dec!(-90)),
+        ];
+        records[1].account_id = "ABC1234.002".into();
+
+        let group = |records: &[RawRecord], grouping| {
+            group_records_reporting_rejected(records, &ImportOptions {
+                grouping,
+                ..Default::default()
+            })
+            .0
+        };
+
+        assert_eq!(group(&records, GroupingKey::default()).len(), 2);
+        assert_eq!(
+            group(&records, GroupingKey {
+                by_account: false,
+                ..Default::default()
+            })
+            .len(),
+            1
+        );
+
+        records[1].operation_type = "TRANSFER".into();
+        records[1].comment = Some("Transfer to ABC1234.001".into());
+
+        assert_eq!(group(&records, GroupingKey::default()).len(), 1);
+    }
+
+    #[test]
+    fn future_record_is_rejected_when_strict() {
+        let records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(100)),
+            record("2", "2021-03-01 10:00:00", dec!(-90)),
+        ];
+        let mut options = ImportOptions {
+            as_of: Some(parse_exante_date("2021-02-01 00:00:00").unwrap()),
+            ..Default::default()
+        };
+
+        let (transactions, rejected) =
+            group_records_reporting_rejected(&records, &options);
+
+        assert_eq!(transactions.len(), 2);
+        assert!(rejected.is_empty());
+
+        options.reject_future = true;
+
+        let (transactions, rejected) =
+            group_records_reporting_rejected(&records, &options);
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].uuid, "2");
+        assert_matches!(&rejected[0].error, RawRecordError::FutureTimestamp(_));
+    }
+
+    #[test]
+    fn rounding_to_asset_scale_is_reported() {
+        let records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(1300.505)),
+            record("2", "2021-01-01 10:00:00", dec!(-2.5)),
+        ];
+        let mut options = ImportOptions::default();
+
+        assert_eq!(check_precision(&records, &options), vec![]);
+
+        options.round_to_asset_scale = true;
+
+        assert_eq!(check_precision(&records, &options), vec![
+            PrecisionWarning {
+                operation_id: "1".parse().unwrap(),
+                lost: dec!(0.005),
+            }
+        ]);
+        assert_eq!(
+            records[0].to_operation(&options).unwrap().value,
+            dec!(1300.5)
+        );
+    }
+
+    #[test]
+    fn unknown_asset_follows_the_policy() {
+        let mut records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(100)),
+            record("2", "2021-01-01 10:00:00", dec!(-90)),
+        ];
+        records[1].asset = "CHF".into();
+
+        let import = |on_unknown_asset| {
+            group_records_reporting_rejected(&records, &ImportOptions {
+                on_unknown_asset,
+                ..Default::default()
+            })
+        };
+
+        let (transactions, rejected) = import(UnknownAssetPolicy::FailRow);
+
+        assert_eq!(transactions[0].operations().len(), 1);
+        assert_eq!(rejected.len(), 1);
+        assert_matches!(&rejected[0].error, RawRecordError::UnknownAsset(asset) if asset == "CHF");
+
+        let (transactions, rejected) = import(UnknownAssetPolicy::Skip);
+
+        assert_eq!(transactions[0].operations().len(), 1);
+        assert!(rejected.is_empty());
+
+        let placeholder = AssetId::Token(TokenId("UNKNOWN".into()));
+        let (transactions, rejected) =
+            import(UnknownAssetPolicy::Placeholder(placeholder.to_owned()));
+
+        assert_eq!(transactions[0].operations().len(), 2);
+        assert_eq!(transactions[0].operations()[1].asset.id(), &placeholder);
+        assert!(rejected.is_empty());
+    }
+
+    #[test]
+    fn user_rule_refines_classification() {
+        let mut salary = record("1", "2021-01-01 10:00:00", dec!(5000));
+        salary.operation_type = "FUNDING/WITHDRAWAL".into();
+        salary.comment = Some("SALARY 2021/01".into());
+
+        let mut options = ImportOptions::default();
+
+        assert_eq!(
+            salary.to_operation(&options).unwrap().kind,
+            OperationKind::Inflow(InflowOperation::Deposit)
+        );
+
+        options.classification.rule(
+            ClassificationRule::new(OperationKind::Inflow(InflowOperation::Income))
+                .comment_contains("salary"),
+        );
+
+        assert_eq!(
+            salary.to_operation(&options).unwrap().kind,
+            OperationKind::Inflow(InflowOperation::Income)
+        );
+    }
+
+    #[test]
+    fn rebate_and_adjustment_are_classified() {
+        let mut rebate = record("1", "2021-01-01 10:00:00", dec!(0.5));
+        rebate.operation_type = "REBATE".into();
+        let mut debited_rebate = record("3", "2021-01-01 10:00:00", dec!(-0.5));
+        debited_rebate.operation_type = "REBATE".into();
+        let mut adjustment = record("2", "2021-01-01 10:00:00", dec!(-1.25));
+        adjustment.operation_type = "ADJUSTMENT".into();
+
+        let rebate = Operation::try_from(&rebate).unwrap();
+        let debited_rebate = Operation::try_from(&debited_rebate).unwrap();
+        let adjustment = Operation::try_from(&adjustment).unwrap();
+
+        assert_eq!(rebate.kind, OperationKind::Inflow(InflowOperation::Rebate));
+        assert_eq!(
+            debited_rebate.kind,
+            OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            })
+        );
+        assert_eq!(
+            adjustment.kind,
+            OperationKind::Adjustment(AdjustmentOperation::Debit)
+        );
+        assert_eq!(adjustment.signed_value(), rust_decimal_macros::dec!(-1.25));
+
+        let tx = TransactionBuilder::default()
+            .add_operation(adjustment)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            crate::tax::classify_tax_category(&tx),
+            crate::tax::TaxCategory::NonTaxable
+        );
+    }
+
+    #[test]
+    fn progress_is_reported_while_streaming() {
+        let file = fs::File::open(DEMO_CSV_FILE_PATH).unwrap();
+        let mut sink = VecSink::default();
+        let mut reports = vec![];
+
+        stream_records_into_sink(
+            read_csv_reader(file),
+            &mut sink,
+            &ImportOptions::default(),
+            Some(&mut |progress| reports.push(progress)),
+        )
+        .unwrap();
+
+        assert_gt!(reports.len(), 0);
+        assert_eq!(
+            reports.last(),
+            Some(&ImportProgress {
+                records_read: 13,
+                records_rejected: 0,
+                transactions_emitted: sink.transactions().len(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejected_record_does_not_drop_its_group() {
+        let records = vec![
+            record("1", "2021-01-01 10:00:00", dec!(100)),
+            RawRecord {
+                isin: "NOT-AN-ISIN".into(),
+                ..record("2", "2021-01-01 10:00:00", dec!(-5))
+            },
+            record("3", "2021-01-01 10:00:00", dec!(-95)),
+        ];
+
+        let (transactions, rejected) =
+            group_records_reporting_rejected(&records, &ImportOptions::default());
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(
+            transactions[0]
+                .operations()
+                .iter()
+                .map(|operation| operation.id.to_string())
+                .collect::<Vec<_>>(),
+            vec!["1", "3"]
+        );
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].uuid, "2");
+        assert_matches!(&rejected[0].error, RawRecordError::ISIN(_));
+    }
+
+    #[test]
+    fn blank_ids_are_synthesized_when_enabled() {
+        let blank = RawRecord {
+            uuid: "".into(),
+            ..record("1", "2021-01-01 10:00:00", dec!(-5))
+        };
+        let options = ImportOptions {
+            synthesize_ids: true,
+            ..Default::default()
+        };
+
+        assert_matches!(
+            blank.to_operation(&ImportOptions::default()),
+            Err(RawRecordError::MissingId)
+        );
+
+        let id = blank.to_operation(&options).unwrap().id;
+
+        assert!(id.to_string().starts_with("SYN-"));
+        assert_eq!(blank.to_operation(&options).unwrap().id, id);
+
+        let synthesized_id = |sum: Decimal| {
+            RawRecord {
+                uuid: "".into(),
+                ..record("1", "2021-01-01 10:00:00", sum)
+            }
+            .to_operation(&options)
+            .unwrap()
+            .id
+        };
+
+        assert_ne!(synthesized_id(dec!(-6)), id);
+        // indistinguishable as floats
+        assert_ne!(synthesized_id(dec!(-5.00000000000000001)), id);
+        assert_eq!(synthesized_id(dec!(-5.0)), id);
+    }
+
+    #[test]
+    fn blank_account_is_rejected() {
+        let blank = RawRecord {
+            account_id: " ".into(),
+            ..record("1", "2021-01-01 10:00:00", dec!(-5))
+        };
+
+        assert_matches!(
+            Operation::try_from(&blank),
+            Err(RawRecordError::MissingAccount)
+        );
+    }
+
+    #[test]
+    fn csv_string_is_imported_into_json() {
+        let contents = fs::read_to_string(DEMO_CSV_FILE_PATH).unwrap();
+
+        let json = import_csv_str_to_json(&contents).unwrap();
+        let transactions: Vec<Transaction> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(transactions.len(), 6);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_grouping_matches_sequential_one() {
+        let records = read_csv_file(Path::new(DEMO_CSV_FILE_PATH)).unwrap();
+        let options = ImportOptions::default();
+
+        let (transactions, _) = group_records_parallel(&records, &options);
+
+        assert_eq!(
+            transactions,
+            group_records_reporting_rejected(&records, &options).0
+        );
+    }
+
+    #[test]
+    fn oversized_group_is_split() {
+        let records = (0 .. 100)
+            .map(|i| record(&i.to_string(), "2021-01-01 10:00:00", dec!(1)))
+            .collect::<Vec<_>>();
+        let options = ImportOptions {
+            max_group_size: Some(10),
+            ..Default::default()
+        };
+
+        let (transactions, _) = group_records_reporting_rejected(&records, &options);
+
+        assert_eq!(transactions.len(), 10);
+        assert!(transactions.iter().all(|tx| tx.operations().len() == 10));
+    }
+
+    #[test]
+    fn second_import_of_overlapping_file_yields_only_new_operations() {
+        let first_export = vec![
+            record("1", "2021-01-01 10:00:00", dec!(-5)),
+            record("2", "2021-01-02 10:00:00", dec!(100)),
+            record("3", "2021-01-02 10:00:00", dec!(-100)),
+        ];
+        let second_export = vec![
+            record("1", "2021-01-01 10:00:00", dec!(-5)),
+            record("2", "2021-01-02 10:00:00", dec!(100)),
+            record("3", "2021-01-02 10:00:00", dec!(-100)),
+            record("4", "2021-01-02 10:00:00", dec!(-1)),
+            record("5", "2021-01-03 10:00:00", dec!(1)),
+            // back-dated correction
+            record("6", "2021-01-01 09:00:00", dec!(5)),
+        ];
+        let options = ImportOptions::default();
+
+        let (transactions, _, state) =
+            import_new_records(&first_export, &options, &ImportState::default());
+
+        assert_eq!(transactions.len(), 2);
+
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["last_seen_operation_ids"].as_array().unwrap().len(), 3);
+
+        let state: ImportState = serde_json::from_value(json).unwrap();
+        let (transactions, _, state) =
+            import_new_records(&second_export, &options, &state);
+
+        let ids = transactions
+            .iter()
+            .flat_map(|tx| tx.iter())
+            .map(|op| op.id.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, vec!["6", "4", "5"]);
+        assert_eq!(
+            state.last_timestamp,
+            parse_exante_date("2021-01-03 10:00:00").unwrap()
+        );
+        assert_eq!(state.last_seen_operation_ids.len(), 6);
+    }
+
     #[test]
     fn group_records() {
-        /**
+        /*
          * Think of using a state machine for the following transitions:
          *
          * Initial State
@@ -0,0 +1,98 @@
+//! Transactions fetched from Exante's HTTP API, which holds the same fields
+//! as the CSV export, see [`exante`](super::exante).
+
+use std::{error::Error, fmt::Debug, fs, io, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer};
+
+use crate::data_sources::exante::{deserialize_exante_sum, ExanteSum, RawRecord};
+
+/// Absent values are written as `None` in the CSV export.
+const CSV_NONE: &str = "None";
+
+/// Reads all records from a file holding the JSON array returned by the API.
+pub fn read_json_file<TPath>(file_path: TPath) -> Result<Vec<RawRecord>, Box<dyn Error>>
+where
+    TPath: AsRef<Path> + Debug,
+{
+    Ok(read_json_reader(fs::File::open(file_path)?)?)
+}
+
+/// Reads records from the JSON array returned by the API.
+///
+/// Records are converted into CSV ones, so they're grouped into transactions
+/// with [`group_records_into_transactions`](super::exante::group_records_into_transactions)
+/// all the same.
+pub fn read_json_reader<R: io::Read>(
+    reader: R,
+) -> Result<Vec<RawRecord>, serde_json::Error> {
+    let records: Vec<ApiRecord> = serde_json::from_reader(reader)?;
+
+    Ok(records.into_iter().map(RawRecord::from).collect())
+}
+
+/// Transaction as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiRecord {
+    id: u64,
+    account_id: String,
+    symbol_id: Option<String>,
+    isin: Option<String>,
+    operation_type: String,
+    /// Milliseconds since the Unix epoch.
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    timestamp: DateTime<Utc>,
+    /// Amount as a string, i.e. `"-1300.5"`.
+    #[serde(deserialize_with = "deserialize_exante_sum")]
+    sum: ExanteSum,
+    asset: String,
+    comment: Option<String>,
+    uuid: String,
+}
+
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let millis = i64::deserialize(deserializer)?;
+
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| de::Error::custom(format!("Invalid timestamp: {}", millis)))
+}
+
+impl From<ApiRecord> for RawRecord {
+    fn from(record: ApiRecord) -> Self {
+        RawRecord {
+            tx_id: record.id.to_string(),
+            account_id: record.account_id,
+            symbol_id: record.symbol_id.unwrap_or_else(|| CSV_NONE.into()),
+            isin: record.isin.unwrap_or_else(|| CSV_NONE.into()),
+            operation_type: record.operation_type,
+            when: record.timestamp,
+            sum: record.sum,
+            asset: record.asset,
+            comment: record.comment,
+            uuid: record.uuid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::exante::{self, group_records_into_transactions};
+
+    #[test]
+    fn api_payload_is_grouped_as_csv_export() {
+        let api_records = read_json_file("input/exante/demo.json").unwrap();
+        let csv_records = exante::read_csv_file("input/exante/demo.csv").unwrap();
+
+        assert_eq!(api_records.len(), csv_records.len());
+        assert_eq!(
+            group_records_into_transactions(&api_records).unwrap(),
+            group_records_into_transactions(&csv_records).unwrap()
+        );
+    }
+}
@@ -1 +1,723 @@
-mod exante;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead, Read},
+    path::Path,
+};
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, ParseError, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    accounting::Trade,
+    asset::SumOverflow,
+    operation::{Operation, OperationKind, OutflowOperation},
+    transaction::{Transaction, TransactionBuilder},
+};
+
+pub mod degiro;
+pub mod exante;
+pub mod exante_api;
+
+/// Record of a data source, i.e. a CSV row.
+pub trait SourceRecord {
+    type Error;
+
+    fn operation(&self) -> Result<Operation, Self::Error>;
+
+    /// Operations the record stands for.
+    ///
+    /// Most sources export one operation per record, which is the default.
+    /// Sources exporting, i.e. a whole trade in a single row override this to
+    /// return all of its legs.
+    fn split_record(&self) -> Result<Vec<Operation>, Self::Error> {
+        Ok(vec![self.operation()?])
+    }
+}
+
+/// Totals an export states about itself, i.e. in a trailer row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trailer {
+    /// Number of records.
+    pub count: usize,
+    /// Sum of the amounts of all records, regardless of their asset, i.e.
+    /// shares bought added to the dollars paid for them. A control total
+    /// rather than a balance.
+    pub net: Decimal,
+}
+
+/// Outcome of comparing imported transactions against the totals the export
+/// states, see [`reconcile`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconciliationReport {
+    pub expected_count: usize,
+    pub actual_count: usize,
+    pub expected_net: Decimal,
+    pub actual_net: Decimal,
+    pub matches: bool,
+}
+
+/// Compares operations of the imported transactions against the totals of
+/// the export, to tell whether any record got lost on the way.
+///
+/// The net is summed across assets the way the export sums the trailer (see
+/// [`Trailer::net`]), so it's only meaningful compared against it. Fails
+/// when it doesn't fit in a [`Decimal`], naming the asset of the operation
+/// it overflowed at.
+pub fn reconcile(
+    transactions: &[Transaction],
+    trailer: &Trailer,
+) -> Result<ReconciliationReport, SumOverflow> {
+    let mut operations = transactions.iter().flat_map(|tx| tx.iter());
+
+    let actual_count = operations.clone().count();
+    let actual_net = operations.try_fold(Decimal::ZERO, |net, operation| {
+        net.checked_add(operation.signed_value())
+            .ok_or_else(|| SumOverflow {
+                asset: operation.asset.id().to_owned(),
+            })
+    })?;
+
+    Ok(ReconciliationReport {
+        expected_count: trailer.count,
+        actual_count,
+        expected_net: trailer.net,
+        actual_net,
+        matches: trailer.count == actual_count && trailer.net == actual_net,
+    })
+}
+
+/// Number of lines searched for the column header by [`skip_preamble`].
+const MAX_PREAMBLE_LINES: usize = 20;
+
+/// Skips the rows some brokers put above the column header, i.e. account
+/// details or a report title.
+///
+/// The header is the first line containing all of the `expected_columns`,
+/// ignoring case.
+/// When none of the first lines does, nothing is skipped, so that the CSV
+/// reader reports the file as it is. Read errors end the search as well, and
+/// are left to the CSV reader.
+pub fn skip_preamble<R: BufRead>(
+    mut reader: R,
+    expected_columns: &[&str],
+) -> io::Chain<io::Cursor<Vec<u8>>, R> {
+    let mut lines = vec![];
+
+    for _ in 0 .. MAX_PREAMBLE_LINES {
+        let start = lines.len();
+
+        if reader.read_until(b'\n', &mut lines).unwrap_or_default() == 0 {
+            break;
+        }
+
+        let line = String::from_utf8_lossy(&lines[start ..]).to_lowercase();
+
+        if expected_columns
+            .iter()
+            .all(|column| line.contains(&column.to_lowercase()))
+        {
+            lines.drain(.. start);
+            break;
+        }
+    }
+
+    io::Cursor::new(lines).chain(reader)
+}
+
+/// Data sources with an importer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SourceKind {
+    Exante,
+    Degiro,
+}
+
+impl SourceKind {
+    const ALL: [SourceKind; 2] = [SourceKind::Exante, SourceKind::Degiro];
+
+    /// Named columns of the source's CSV header.
+    fn header_columns(self) -> &'static [&'static str] {
+        match self {
+            SourceKind::Exante => &[
+                "Transaction ID",
+                "Account ID",
+                "Symbol ID",
+                "ISIN",
+                "Operation type",
+                "When",
+                "Sum",
+                "Asset",
+                "EUR equivalent",
+                "Comment",
+                "UUID",
+                "Parent UUID",
+            ],
+            SourceKind::Degiro => &[
+                "Date",
+                "Time",
+                "Value date",
+                "Product",
+                "ISIN",
+                "Description",
+                "FX",
+                "Change",
+                "Balance",
+                "Order Id",
+            ],
+        }
+    }
+}
+
+/// Source a file was recognized as, see [`detect_source`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectedSource {
+    pub kind: SourceKind,
+    /// Share of the source's columns found in the header, from 0 to 1.
+    pub confidence: f32,
+}
+
+/// Lowest confidence a source is detected with.
+const MIN_DETECTION_CONFIDENCE: f32 = 0.5;
+
+/// Magic bytes opening every gzip stream.
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens the file for reading.
+///
+/// With the `gzip` feature enabled, gzipped files (i.e. `demo.csv.gz`) are
+/// detected by their magic bytes and decompressed on the fly, so that every
+/// importer reads them the same as plain ones.
+pub fn open_file<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    #[allow(unused_mut)]
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+
+    #[cfg(feature = "gzip")]
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC_BYTES) {
+        let decoder = flate2::read::GzDecoder::new(reader);
+
+        return Ok(Box::new(io::BufReader::new(decoder)));
+    }
+
+    Ok(Box::new(reader))
+}
+
+/// Tells which importer reads the file, by the columns of its header.
+///
+/// Gzipped files are looked into as well, see [`open_file`]. Files which
+/// can't be read aren't recognized.
+pub fn detect_source<P: AsRef<Path>>(path: P) -> Option<DetectedSource> {
+    detect_source_reader(open_file(path).ok()?)
+}
+
+/// Same as [`detect_source`], but reads the file's contents from `reader`.
+///
+/// Every line up to the header (see [`skip_preamble`]) is split at tabs,
+/// commas and semicolons, and the source with the most of its columns in a
+/// line wins, provided that's at least half of them.
+pub fn detect_source_reader<R: BufRead>(reader: R) -> Option<DetectedSource> {
+    let mut detected: Option<DetectedSource> = None;
+
+    for line in reader.lines().take(MAX_PREAMBLE_LINES) {
+        let Ok(line) = line else {
+            break;
+        };
+
+        let cells = line
+            .split(['\t', ',', ';'])
+            .map(|cell| cell.trim().trim_matches('"').to_lowercase())
+            .collect::<HashSet<_>>();
+
+        for kind in SourceKind::ALL {
+            let columns = kind.header_columns();
+            let found = columns
+                .iter()
+                .filter(|column| cells.contains(&column.to_lowercase()))
+                .count();
+            let confidence = found as f32 / columns.len() as f32;
+
+            if confidence >= MIN_DETECTION_CONFIDENCE
+                && detected.is_none_or(|detected| confidence > detected.confidence)
+            {
+                detected = Some(DetectedSource { kind, confidence });
+            }
+        }
+    }
+
+    detected
+}
+
+/// Combines date and time kept in separate columns, as some brokers export
+/// them, into a UTC date time.
+///
+/// The date is parsed with the given format, i.e. `%d-%m-%Y`, and the time
+/// either as `HH:MM:SS` or `HH:MM`.
+pub fn combine_split_datetime(
+    date: &str,
+    time: &str,
+    date_format: &str,
+) -> Result<DateTime<Utc>, ParseError> {
+    let date = NaiveDate::parse_from_str(date.trim(), date_format)?;
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time.trim(), "%H:%M"))?;
+
+    Ok(date.and_time(time).and_utc())
+}
+
+/// Merges operations imported from several files into chronological order,
+/// dropping duplicates, i.e. when exports overlap.
+///
+/// Operations are duplicates when they share id, execution time and asset
+/// identity (see [`Asset::same_identity`](crate::asset::Asset::same_identity)),
+/// so an asset named differently by each file doesn't matter. The first
+/// occurrence is kept.
+pub fn merge_operations<TBatches>(batches: TBatches) -> Vec<Operation>
+where
+    TBatches: IntoIterator<Item = Vec<Operation>>,
+{
+    let mut operations = batches.into_iter().flatten().collect::<Vec<_>>();
+
+    // stable, so that duplicates keep the order of their batches
+    operations.sort_by(|a, b| (a.executed_at, &a.id).cmp(&(b.executed_at, &b.id)));
+
+    let mut merged: Vec<Operation> = Vec::with_capacity(operations.len());
+
+    for operation in operations {
+        let is_duplicate = merged
+            .iter()
+            .rev()
+            .take_while(|merged| merged.executed_at == operation.executed_at)
+            .any(|merged| {
+                merged.id == operation.id && merged.asset.same_identity(&operation.asset)
+            });
+
+        if !is_duplicate {
+            merged.push(operation);
+        }
+    }
+
+    merged
+}
+
+/// How to combine transactions of the same account imported from two
+/// sources, see [`merge_transactions`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MergeStrategy {
+    /// Keeps all the first source's transactions, and the second source's
+    /// ones only outside the period covered by the first source.
+    PreferFirst,
+    /// Same as [`MergeStrategy::PreferFirst`] with the sources swapped.
+    PreferSecond,
+    /// Keeps the transactions of both sources, dropping the second source's
+    /// ones with the fingerprint of a first source's one.
+    Union,
+}
+
+/// Merges transactions imported from two sources, in chronological order.
+///
+/// Sources may describe the same transaction differently, i.e. with other
+/// operation ids, so the preferring strategies resolve conflicts by period:
+/// the preferred source is taken as complete for the period it covers. With
+/// [`MergeStrategy::Union`], transactions with equal
+/// [fingerprints](Transaction::fingerprint) are duplicates and the first
+/// source's version is kept, even though descriptions may differ. When the
+/// operation sets differ, fingerprints do as well, and both versions are
+/// kept.
+pub fn merge_transactions(
+    a: Vec<Transaction>,
+    b: Vec<Transaction>,
+    strategy: MergeStrategy,
+) -> Vec<Transaction> {
+    let mut merged = match strategy {
+        MergeStrategy::PreferFirst => prefer(a, b),
+        MergeStrategy::PreferSecond => prefer(b, a),
+        MergeStrategy::Union => {
+            let fingerprints = a
+                .iter()
+                .map(Transaction::fingerprint)
+                .collect::<HashSet<_>>();
+
+            a.into_iter()
+                .chain(
+                    b.into_iter()
+                        .filter(|tx| !fingerprints.contains(&tx.fingerprint())),
+                )
+                .collect()
+        }
+    };
+
+    merged.sort_by_key(|tx| tx.started_at());
+
+    merged
+}
+
+fn prefer(preferred: Vec<Transaction>, other: Vec<Transaction>) -> Vec<Transaction> {
+    let started_at = preferred.iter().map(|tx| tx.started_at()).min();
+    let finished_at = preferred.iter().map(|tx| tx.finished_at()).max();
+
+    let Some((started_at, finished_at)) = started_at.zip(finished_at) else {
+        return other;
+    };
+
+    preferred
+        .into_iter()
+        .chain(
+            other.into_iter().filter(|tx| {
+                tx.finished_at() < started_at || tx.started_at() > finished_at
+            }),
+        )
+        .collect()
+}
+
+/// Reattaches fees which ended up in transactions of their own, i.e. charged
+/// a few seconds after their trade, to the latest trade on the same ledger
+/// and in the same currency finished at most `window` before them.
+///
+/// Transactions made of fees only are orphans, and ones making a [`Trade`]
+/// are trades. Orphans with no trade in the window are kept as they are. The
+/// output is in chronological order.
+pub fn merge_fee_into_trade(
+    mut transactions: Vec<Transaction>,
+    window: Duration,
+) -> Vec<Transaction> {
+    transactions.sort_by_key(|tx| tx.started_at());
+
+    let is_orphan_fee = |tx: &Transaction| {
+        tx.iter().all(|op| {
+            matches!(
+                op.kind,
+                OperationKind::Outflow(OutflowOperation::Fee { .. })
+            )
+        })
+    };
+    let (orphans, mut merged): (Vec<_>, Vec<_>) =
+        transactions.into_iter().partition(is_orphan_fee);
+    let is_trade = merged
+        .iter()
+        .map(|tx| Trade::from_transaction(tx).is_some())
+        .collect::<Vec<_>>();
+
+    for fee in orphans {
+        let trade = merged
+            .iter()
+            .enumerate()
+            .filter(|&(i, trade)| {
+                is_trade[i]
+                    && trade.finished_at() <= fee.started_at()
+                    && fee.started_at() - trade.finished_at() <= window
+                    && fee.iter().all(|op| {
+                        trade.ledgers().contains(&op.ledger)
+                            && trade.contains_asset(op.asset.id())
+                    })
+            })
+            .max_by_key(|(_, trade)| trade.finished_at())
+            .map(|(i, _)| i);
+
+        match trade {
+            Some(i) => merged[i] = merge_transaction(&merged[i], fee),
+            None => merged.push(fee),
+        }
+    }
+
+    merged.sort_by_key(|tx| tx.started_at());
+
+    merged
+}
+
+/// Transaction with the operations, tags and metadata of both, the ones of
+/// `tx` winning on conflicting keys.
+fn merge_transaction(tx: &Transaction, other: Transaction) -> Transaction {
+    let mut tx_builder = TransactionBuilder::default();
+
+    for operation in tx.iter().chain(other.iter()) {
+        tx_builder.add_operation(operation.to_owned());
+    }
+
+    for tag in tx.tags.iter().chain(&other.tags) {
+        tx_builder.tag(tag);
+    }
+
+    for (key, value) in other.metadata.iter().chain(&tx.metadata) {
+        tx_builder.meta(key, value);
+    }
+
+    // both transactions have operations
+    tx_builder.build().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::{Asset, AssetId, FiatCurrency, Money},
+        operation::{test::operation, FeeCategory, InflowOperation},
+    };
+
+    #[test]
+    fn reconciling_beyond_decimal_range_is_an_overflow() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let deposit = |id: &str| Operation {
+            id: id.parse().unwrap(),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                usd.to_owned(),
+                Decimal::MAX,
+            )
+        };
+        let tx =
+            Transaction::from_operations(vec![deposit("OP1"), deposit("OP2")]).unwrap();
+        let trailer = Trailer {
+            count: 2,
+            net: Decimal::MAX,
+        };
+
+        assert_eq!(reconcile(&[tx], &trailer), Err(SumOverflow { asset: usd }));
+    }
+
+    #[test]
+    fn source_is_detected_by_its_header() {
+        let fixtures = [
+            ("input/exante/demo.csv", SourceKind::Exante),
+            ("input/exante/alternate_headers.csv", SourceKind::Exante),
+            ("input/exante/trailer.csv", SourceKind::Exante),
+            ("input/degiro/demo.csv", SourceKind::Degiro),
+            ("input/degiro/preamble.csv", SourceKind::Degiro),
+        ];
+
+        for (path, kind) in fixtures {
+            let detected = detect_source(path).unwrap();
+
+            assert_eq!(detected.kind, kind, "{}", path);
+            assert_eq!(detected.confidence, 1.0, "{}", path);
+        }
+
+        let revolut = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance\n";
+
+        assert_eq!(detect_source_reader(revolut.as_bytes()), None);
+        assert_eq!(detect_source("input/exante/demo.json"), None);
+        assert_eq!(detect_source("input/missing.csv"), None);
+    }
+
+    #[test]
+    fn preamble_is_skipped_up_to_the_header() {
+        let contents = "Account: ABC1234\nPeriod: 2021\nDate,Sum\n2021-01-01,1\n";
+        let read = |expected_columns: &[&str]| {
+            let mut output = String::new();
+
+            skip_preamble(contents.as_bytes(), expected_columns)
+                .read_to_string(&mut output)
+                .unwrap();
+
+            output
+        };
+
+        assert_eq!(read(&["Date", "Sum"]), "Date,Sum\n2021-01-01,1\n");
+        assert_eq!(read(&["UUID"]), contents);
+    }
+
+    /// Deposit of `value` on the given day of January 2021.
+    fn deposit(id: &str, day: u32, value: Decimal) -> Transaction {
+        let operation = Operation {
+            id: id.parse().unwrap(),
+            executed_at: Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap(),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::USD),
+                value,
+            )
+        };
+
+        Transaction::from_operations(vec![operation]).unwrap()
+    }
+
+    /// Exports overlapping on the 2nd and 3rd, where the second one describes
+    /// the deposit of the 3rd with a different id.
+    fn overlapping_exports() -> (Vec<Transaction>, Vec<Transaction>) {
+        let a = vec![
+            deposit("A1", 1, dec!(1)),
+            deposit("A2", 2, dec!(2)),
+            deposit("A3", 3, dec!(3)),
+        ];
+        let b = vec![
+            deposit("A2", 2, dec!(2)),
+            deposit("B3", 3, dec!(3)),
+            deposit("B4", 4, dec!(4)),
+        ];
+
+        (a, b)
+    }
+
+    fn ids(transactions: &[Transaction]) -> Vec<String> {
+        transactions
+            .iter()
+            .flat_map(|tx| tx.iter().map(|operation| operation.id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_preferring_first_source() {
+        let (a, b) = overlapping_exports();
+
+        let merged = merge_transactions(a, b, MergeStrategy::PreferFirst);
+
+        assert_eq!(ids(&merged), vec!["A1", "A2", "A3", "B4"]);
+    }
+
+    #[test]
+    fn merge_preferring_second_source() {
+        let (a, b) = overlapping_exports();
+
+        let merged = merge_transactions(a, b, MergeStrategy::PreferSecond);
+
+        assert_eq!(ids(&merged), vec!["A1", "A2", "B3", "B4"]);
+    }
+
+    #[test]
+    fn merge_union_drops_equal_fingerprints_only() {
+        let (a, b) = overlapping_exports();
+
+        let merged = merge_transactions(a, b, MergeStrategy::Union);
+
+        assert_eq!(ids(&merged), vec!["A1", "A2", "A3", "B3", "B4"]);
+    }
+
+    /// Row with both the security acquired and the cash paid for it.
+    struct TradeRow {
+        isin: &'static str,
+        quantity: Decimal,
+        cost: Decimal,
+    }
+
+    impl SourceRecord for TradeRow {
+        type Error = ();
+
+        fn operation(&self) -> Result<Operation, Self::Error> {
+            unreachable!("trade rows are always split")
+        }
+
+        fn split_record(&self) -> Result<Vec<Operation>, Self::Error> {
+            let security = AssetId::Security(self.isin.parse().map_err(|_| ())?);
+            let usd = AssetId::Currency(FiatCurrency::USD);
+
+            Ok(vec![
+                Operation {
+                    id: "OP1-security".parse().unwrap(),
+                    ..operation(
+                        OperationKind::Inflow(InflowOperation::Deposit),
+                        security,
+                        self.quantity,
+                    )
+                },
+                Operation {
+                    id: "OP1-cash".parse().unwrap(),
+                    ..operation(
+                        OperationKind::Outflow(OutflowOperation::Withdrawal),
+                        usd,
+                        self.cost,
+                    )
+                },
+            ])
+        }
+    }
+
+    #[test]
+    fn single_row_is_split_into_trade_legs() {
+        let row = TradeRow {
+            isin: "US0378331005",
+            quantity: dec!(10),
+            cost: dec!(1200),
+        };
+
+        let tx = Transaction::from_operations(row.split_record().unwrap()).unwrap();
+        let trade = Trade::from_transaction(&tx).unwrap();
+
+        assert_eq!(tx.operations().len(), 2);
+        assert_eq!(trade.quantity, dec!(10));
+        assert_eq!(trade.value, Money::new(dec!(1200), FiatCurrency::USD));
+    }
+
+    #[test]
+    fn split_date_and_time_are_combined() {
+        assert_eq!(
+            combine_split_datetime("01-02-2021", "14:30:00", "%d-%m-%Y").unwrap(),
+            Utc.with_ymd_and_hms(2021, 2, 1, 14, 30, 0).unwrap()
+        );
+        assert_eq!(
+            combine_split_datetime("01-02-2021", "14:30", "%d-%m-%Y").unwrap(),
+            Utc.with_ymd_and_hms(2021, 2, 1, 14, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn orphan_fee_is_merged_into_preceding_trade() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let aapl = AssetId::Security("US0378331005".parse().unwrap());
+        let at = |second| Utc.with_ymd_and_hms(2021, 1, 1, 10, 0, second).unwrap();
+        let trade = TransactionBuilder::default()
+            .add_operation(operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                aapl,
+                dec!(10),
+            ))
+            .add_operation(Operation {
+                id: "OP2".parse().unwrap(),
+                ..operation(
+                    OperationKind::Outflow(OutflowOperation::Purchase),
+                    usd.to_owned(),
+                    dec!(1300.5),
+                )
+            })
+            .build()
+            .unwrap();
+        let fee = |id: &str, second| {
+            Transaction::from_operations(vec![Operation {
+                id: id.parse().unwrap(),
+                executed_at: at(second),
+                ..operation(
+                    OperationKind::Outflow(OutflowOperation::Fee {
+                        category: FeeCategory::Commission,
+                    }),
+                    usd.to_owned(),
+                    dec!(2),
+                )
+            }])
+            .unwrap()
+        };
+
+        let merged = merge_fee_into_trade(
+            vec![fee("OP3", 3), trade, fee("OP4", 30)],
+            Duration::seconds(10),
+        );
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].operation_count(), 3);
+        assert_eq!(merged[0].finished_at(), at(3));
+        assert_eq!(
+            Trade::from_transaction(&merged[0]).unwrap().value,
+            Money::new(dec!(1302.5), FiatCurrency::USD)
+        );
+        assert_eq!(merged[1].operations()[0].id, "OP4".parse().unwrap());
+    }
+
+    #[test]
+    fn merge_drops_operations_of_the_same_asset_named_differently() {
+        let id = AssetId::Security("US0378331005".parse().unwrap());
+        let deposit = OperationKind::Inflow(InflowOperation::Deposit);
+        let first = Operation {
+            asset: Asset::new(id.to_owned(), "Apple Inc".into()),
+            ..operation(deposit.to_owned(), id.to_owned(), dec!(10))
+        };
+        let second = Operation {
+            asset: Asset::new(id.to_owned(), "APPLE INC".into()),
+            ..operation(deposit, id, dec!(10))
+        };
+
+        let merged = merge_operations([vec![first.to_owned()], vec![second]]);
+
+        assert_eq!(merged, vec![first]);
+    }
+}
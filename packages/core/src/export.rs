@@ -0,0 +1,179 @@
+//! Writes transactions in formats of other personal finance tools.
+
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::{
+    asset::AssetId,
+    operation::{InflowOperation, Operation, OperationKind, OutflowOperation},
+    transaction::Transaction,
+};
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+const OFX_DATE_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Writes operations in fiat currencies as an OFX 2 bank statement, with a
+/// statement per ledger and currency.
+///
+/// Each operation makes a `<STMTTRN>` entry: its id is the `FITID`, its
+/// description the `MEMO`, and the amount is negative for outflows.
+/// Operations in securities or tokens aren't part of a bank statement and
+/// are left out.
+pub fn write_ofx<W: Write>(txs: &[Transaction], mut w: W) -> Result<(), ExportError> {
+    let mut statements: BTreeMap<(&str, String), Vec<&Operation>> = BTreeMap::new();
+
+    for operation in txs.iter().flat_map(|tx| tx.iter()) {
+        if let AssetId::Currency(currency) = operation.asset.id() {
+            statements
+                .entry((operation.ledger.name(), currency.to_string()))
+                .or_default()
+                .push(operation);
+        }
+    }
+
+    writeln!(
+        w,
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#
+    )?;
+    writeln!(
+        w,
+        r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#
+    )?;
+    writeln!(w, "<OFX>")?;
+    writeln!(w, "<SIGNONMSGSRSV1><SONRS>")?;
+    writeln!(
+        w,
+        "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+    )?;
+    writeln!(
+        w,
+        "<DTSERVER>{}</DTSERVER>",
+        Utc::now().format(OFX_DATE_FORMAT)
+    )?;
+    writeln!(w, "<LANGUAGE>ENG</LANGUAGE>")?;
+    writeln!(w, "</SONRS></SIGNONMSGSRSV1>")?;
+    writeln!(w, "<BANKMSGSRSV1>")?;
+
+    for (i, ((ledger, currency), mut operations)) in statements.into_iter().enumerate() {
+        operations.sort();
+
+        let date = |at: DateTime<Utc>| at.format(OFX_DATE_FORMAT);
+
+        writeln!(w, "<STMTTRNRS>")?;
+        writeln!(w, "<TRNUID>{}</TRNUID>", i + 1)?;
+        writeln!(
+            w,
+            "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+        )?;
+        writeln!(w, "<STMTRS>")?;
+        writeln!(w, "<CURDEF>{}</CURDEF>", currency)?;
+        // ledgers don't know their bank, only their name
+        writeln!(
+            w,
+            "<BANKACCTFROM><BANKID>0</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>",
+            escape(ledger)
+        )?;
+        writeln!(w, "<BANKTRANLIST>")?;
+        writeln!(w, "<DTSTART>{}</DTSTART>", date(operations[0].executed_at))?;
+        writeln!(
+            w,
+            "<DTEND>{}</DTEND>",
+            date(operations[operations.len() - 1].executed_at)
+        )?;
+
+        for operation in operations {
+            writeln!(w, "<STMTTRN>")?;
+            writeln!(w, "<TRNTYPE>{}</TRNTYPE>", transaction_type(operation))?;
+            writeln!(w, "<DTPOSTED>{}</DTPOSTED>", date(operation.executed_at))?;
+            writeln!(w, "<TRNAMT>{}</TRNAMT>", operation.signed_value())?;
+            writeln!(w, "<FITID>{}</FITID>", escape(&operation.id.to_string()))?;
+
+            if let Some(description) = &operation.description {
+                writeln!(w, "<MEMO>{}</MEMO>", escape(description))?;
+            }
+
+            writeln!(w, "</STMTTRN>")?;
+        }
+
+        writeln!(w, "</BANKTRANLIST>")?;
+        writeln!(w, "</STMTRS>")?;
+        writeln!(w, "</STMTTRNRS>")?;
+    }
+
+    writeln!(w, "</BANKMSGSRSV1>")?;
+    writeln!(w, "</OFX>")?;
+
+    Ok(())
+}
+
+fn transaction_type(operation: &Operation) -> &'static str {
+    match operation.kind {
+        OperationKind::Inflow(InflowOperation::Dividend) => "DIV",
+        OperationKind::Inflow(InflowOperation::Interest) => "INT",
+        OperationKind::Outflow(OutflowOperation::Fee { .. }) => "FEE",
+        _ if operation.signed_value().is_sign_negative() => "DEBIT",
+        _ => "CREDIT",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::FiatCurrency,
+        operation::{test::operation, FeeCategory},
+    };
+
+    #[test]
+    fn operations_are_exported_as_signed_entries() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let tx = Transaction::from_operations(vec![
+            Operation {
+                description: Some("Deposit from Bank & Co".into()),
+                ..operation(
+                    OperationKind::Inflow(InflowOperation::Deposit),
+                    usd.to_owned(),
+                    dec!(100),
+                )
+            },
+            Operation {
+                id: "OP2".parse().unwrap(),
+                ..operation(
+                    OperationKind::Outflow(OutflowOperation::Fee {
+                        category: FeeCategory::Commission,
+                    }),
+                    usd,
+                    dec!(2),
+                )
+            },
+        ])
+        .unwrap();
+
+        let mut ofx = vec![];
+        write_ofx(&[tx], &mut ofx).unwrap();
+        let ofx = String::from_utf8(ofx).unwrap();
+
+        assert_eq!(ofx.matches("<STMTTRN>").count(), 2);
+        assert_eq!(ofx.matches("</STMTTRN>").count(), 2);
+        assert!(ofx.contains(
+            "<TRNTYPE>CREDIT</TRNTYPE>\n<DTPOSTED>20210101100000</DTPOSTED>\n<TRNAMT>100</TRNAMT>\n<FITID>OP1</FITID>\n<MEMO>Deposit from Bank &amp; Co</MEMO>"
+        ));
+        assert!(ofx.contains("<TRNAMT>-2</TRNAMT>\n<FITID>OP2</FITID>"));
+        assert!(ofx.contains("<CURDEF>USD</CURDEF>"));
+    }
+}
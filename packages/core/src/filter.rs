@@ -0,0 +1,181 @@
+//! Declarative, reusable filters over operations.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    asset::{AssetClass, AssetId},
+    ledger::Ledger,
+    operation::{Operation, OperationKind},
+};
+
+/// Matches operations meeting all the configured criteria. A filter with no
+/// criteria matches every operation.
+///
+/// # Example
+/// ```
+/// use delfin::{
+///     filter::OperationFilter,
+///     operation::{InflowOperation, OperationKind},
+/// };
+///
+/// let dividends = OperationFilter::new()
+///     .kind(OperationKind::Inflow(InflowOperation::Dividend))
+///     .into_predicate();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OperationFilter {
+    kind: Option<OperationKind>,
+    asset: Option<AssetId>,
+    asset_class: Option<AssetClass>,
+    between: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ledger: Option<Ledger>,
+}
+
+impl OperationFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn kind(mut self, kind: OperationKind) -> Self {
+        self.kind = Some(kind);
+
+        self
+    }
+
+    pub fn asset(mut self, asset: AssetId) -> Self {
+        self.asset = Some(asset);
+
+        self
+    }
+
+    pub fn asset_class(mut self, asset_class: AssetClass) -> Self {
+        self.asset_class = Some(asset_class);
+
+        self
+    }
+
+    /// Operations executed within `[from, to)`.
+    pub fn between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.between = Some((from, to));
+
+        self
+    }
+
+    /// Operations in the ledger or any of its descendants.
+    pub fn ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = Some(ledger);
+
+        self
+    }
+
+    pub fn matches(&self, operation: &Operation) -> bool {
+        self.kind
+            .as_ref()
+            .is_none_or(|kind| &operation.kind == kind)
+            && self
+                .asset
+                .as_ref()
+                .is_none_or(|asset| operation.asset.id() == asset)
+            && self
+                .asset_class
+                .is_none_or(|asset_class| operation.asset.class() == asset_class)
+            && self.between.is_none_or(|(from, to)| {
+                from <= operation.executed_at && operation.executed_at < to
+            })
+            && self.ledger.as_ref().is_none_or(|ledger| {
+                &operation.ledger == ledger || operation.ledger.is_descendant_of(ledger)
+            })
+    }
+
+    pub fn into_predicate(self) -> impl Fn(&Operation) -> bool {
+        move |operation| self.matches(operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::FiatCurrency,
+        operation::{test::operation, InflowOperation, OutflowOperation},
+        transaction::TransactionBuilder,
+    };
+
+    #[test]
+    fn kind_and_date_filters_are_combined() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let dividend_kind = OperationKind::Inflow(InflowOperation::Dividend);
+        let at = |day| Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap();
+
+        let mut early_dividend =
+            operation(dividend_kind.to_owned(), usd.to_owned(), dec!(1));
+        early_dividend.executed_at = at(1);
+        let mut dividend = operation(dividend_kind.to_owned(), usd.to_owned(), dec!(2));
+        dividend.executed_at = at(2);
+        let mut tax = operation(
+            OperationKind::Outflow(OutflowOperation::Cost),
+            usd,
+            dec!(0.3),
+        );
+        tax.executed_at = at(2);
+
+        let tx = TransactionBuilder::default()
+            .add_operation(early_dividend)
+            .add_operation(dividend)
+            .add_operation(tax)
+            .build()
+            .unwrap();
+
+        let filter = OperationFilter::new()
+            .kind(dividend_kind)
+            .between(at(2), at(3));
+
+        let matching = tx.operations_matching(&filter).collect::<Vec<_>>();
+
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].value, dec!(2));
+
+        let is_matching = filter.into_predicate();
+
+        assert_eq!(
+            tx.operations().iter().filter(|op| is_matching(op)).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn ledger_filter_matches_descendants() {
+        let mut operation = operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(1),
+        );
+        operation.ledger = Ledger::new("Assets:Brokerage:USD");
+
+        assert!(OperationFilter::new()
+            .ledger(Ledger::new("Assets"))
+            .matches(&operation));
+        assert!(!OperationFilter::new()
+            .ledger(Ledger::new("Liabilities"))
+            .matches(&operation));
+    }
+
+    #[test]
+    fn asset_class_filter_matches_class_of_operation_asset() {
+        let operation = operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(1),
+        );
+
+        assert!(OperationFilter::new()
+            .asset_class(AssetClass::Fiat)
+            .matches(&operation));
+        assert!(!OperationFilter::new()
+            .asset_class(AssetClass::Security)
+            .matches(&operation));
+    }
+}
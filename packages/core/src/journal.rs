@@ -0,0 +1,59 @@
+//! Building blocks of plain-text accounting journals, i.e. beancount or
+//! ledger files.
+
+use rust_decimal::Decimal;
+
+use crate::{asset::AssetId, ledger::Ledger, operation::Operation};
+
+/// Change of an account's balance in a single commodity, as made by an
+/// operation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JournalPosting {
+    pub account: Ledger,
+    pub commodity: AssetId,
+    /// Negative when the commodity leaves the account.
+    pub amount: Decimal,
+}
+
+impl From<&Operation> for JournalPosting {
+    fn from(operation: &Operation) -> Self {
+        Self {
+            account: operation.ledger.to_owned(),
+            commodity: operation.asset.id().to_owned(),
+            amount: operation.signed_value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::FiatCurrency,
+        operation::{test::operation, InflowOperation, OperationKind, OutflowOperation},
+    };
+
+    #[test]
+    fn posting_amount_is_signed_by_direction() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let deposit = operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            usd.to_owned(),
+            dec!(100),
+        );
+        let withdrawal = operation(
+            OperationKind::Outflow(OutflowOperation::Withdrawal),
+            usd.to_owned(),
+            dec!(40),
+        );
+
+        let posting = JournalPosting::from(&deposit);
+
+        assert_eq!(posting.account, Ledger::new("Brokerage"));
+        assert_eq!(posting.commodity, usd);
+        assert_eq!(posting.amount, dec!(100));
+        assert_eq!(JournalPosting::from(&withdrawal).amount, dec!(-40));
+    }
+}
@@ -1,27 +1,238 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "std")]
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(feature = "std")]
+use crate::{
+    asset::{add_checked, AssetId, FiatCurrency, SumOverflow},
+    transaction::Transaction,
+};
+
 /// Keeps information about a ledger which is a wrapper for transactions.
 ///
+/// Ledgers can form a hierarchy, with path segments delimited by colons,
+/// i.e. `Assets:Brokerage:USD` is a child of `Assets:Brokerage`.
+///
 /// # Example
 /// ```
 /// use delfin::ledger::Ledger;
 ///
 /// let ledger = Ledger::new("TKO's trading account");
-/// ```
 ///
+/// let ledger = "Assets:Brokerage:USD".parse::<Ledger>().unwrap();
+/// assert_eq!(ledger.parent(), Some(Ledger::new("Assets:Brokerage")));
+/// ```
 ///
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// Ledgers are equal when their names are. Importers build them with
+/// [`Ledger::canonical`], so that an account id spelled differently across
+/// exports (i.e. `" ABC123 "` and `"ABC123"`) makes a single ledger. They're
+/// ordered by name too.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Ledger(String);
 
+pub const LEDGER_PATH_DELIMITER: char = ':';
+
+/// Whether [`Ledger::canonical_with`] tells apart names differing in case only.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CaseFolding {
+    #[default]
+    Sensitive,
+    /// Names are upper-cased, as account ids usually are.
+    Insensitive,
+}
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("Empty segment in ledger path: {0}")]
+    EmptySegment(String),
+}
+
+impl FromStr for Ledger {
+    type Err = LedgerError;
+
+    /// Parses a colon-delimited path, rejecting empty segments.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split(LEDGER_PATH_DELIMITER)
+            .any(|segment| segment.trim().is_empty())
+        {
+            return Err(LedgerError::EmptySegment(s.into()));
+        }
+
+        Ok(Ledger::new(s))
+    }
+}
+
 impl Ledger {
     pub fn new(name: &str) -> Self {
         Self(name.to_owned())
     }
+
+    /// Ledger of the name with whitespace normalized: trimmed around each
+    /// path segment, with runs of whitespace within replaced by a space.
+    pub fn canonical(name: &str) -> Self {
+        Self::canonical_with(name, CaseFolding::Sensitive)
+    }
+
+    /// Same as [`Ledger::canonical`], but folds case as well if asked to.
+    pub fn canonical_with(name: &str, case_folding: CaseFolding) -> Self {
+        let name = name
+            .split(LEDGER_PATH_DELIMITER)
+            .map(|segment| segment.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join(&LEDGER_PATH_DELIMITER.to_string());
+
+        match case_folding {
+            CaseFolding::Sensitive => Self(name),
+            CaseFolding::Insensitive => Self(name.to_uppercase()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split(LEDGER_PATH_DELIMITER)
+    }
+
+    /// Ledger one level up the hierarchy, or `None` for a top-level ledger.
+    pub fn parent(&self) -> Option<Ledger> {
+        self.0
+            .rsplit_once(LEDGER_PATH_DELIMITER)
+            .map(|(parent, _)| Ledger::new(parent))
+    }
+
+    /// Whether the ledger is below `ancestor` in the hierarchy. A ledger is
+    /// not a descendant of itself.
+    pub fn is_descendant_of(&self, ancestor: &Ledger) -> bool {
+        self.0
+            .strip_prefix(ancestor.name())
+            .is_some_and(|rest| rest.starts_with(LEDGER_PATH_DELIMITER))
+    }
+}
+
+/// Ledger truncated to its first `depth` segments, or `None` when the ledger
+/// has no more than `depth` segments.
+#[cfg(feature = "std")]
+fn ancestor_at_depth(ledger: &Ledger, depth: usize) -> Option<Ledger> {
+    let segments = ledger.segments().collect::<Vec<_>>();
+
+    (segments.len() > depth).then(|| {
+        Ledger::new(&segments[.. depth].join(&LEDGER_PATH_DELIMITER.to_string()))
+    })
+}
+
+/// Sums balances of each ledger into all its ancestors, down to ledgers of
+/// `depth` segments.
+///
+/// Every ledger with up to `depth` segments gets the balance of its whole
+/// subtree, i.e. for depth `1` only the top-level ledgers are returned.
+/// Deeper ledgers are rolled up into their ancestors and not returned.
+/// Leaf ledgers within the depth are returned with their own balance.
+#[cfg(feature = "std")]
+pub fn rollup_balances(
+    balances: &HashMap<Ledger, HashMap<AssetId, Decimal>>,
+    depth: usize,
+) -> Result<HashMap<Ledger, HashMap<AssetId, Decimal>>, SumOverflow> {
+    let mut rolled_up: HashMap<Ledger, HashMap<AssetId, Decimal>> = HashMap::new();
+
+    if depth == 0 {
+        return Ok(rolled_up);
+    }
+
+    for (ledger, ledger_balances) in balances {
+        let deepest =
+            ancestor_at_depth(ledger, depth).unwrap_or_else(|| ledger.to_owned());
+
+        let ledgers = core::iter::successors(Some(deepest), Ledger::parent);
+
+        for ledger in ledgers {
+            let totals = rolled_up.entry(ledger).or_default();
+
+            for (asset, balance) in ledger_balances {
+                add_checked(totals, asset, *balance)?;
+            }
+        }
+    }
+
+    Ok(rolled_up)
+}
+
+/// Fiat currencies of the operations booked on each ledger.
+#[cfg(feature = "std")]
+pub fn infer_ledger_currencies(
+    txs: &[Transaction],
+) -> HashMap<Ledger, HashSet<FiatCurrency>> {
+    let mut currencies: HashMap<Ledger, HashSet<FiatCurrency>> = HashMap::new();
+
+    for operation in txs.iter().flatten() {
+        if let AssetId::Currency(currency) = operation.asset.id() {
+            currencies
+                .entry(operation.ledger.to_owned())
+                .or_default()
+                .insert(currency.to_owned());
+        }
+    }
+
+    currencies
+}
+
+/// Which currencies a ledger may hold.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CurrencyPolicy {
+    #[default]
+    Mixed,
+    /// Every ledger holds one currency, i.e. a USD cash ledger never holds
+    /// EUR.
+    SingleCurrency,
+}
+
+/// Ledger holding more currencies than its policy allows.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+pub struct MixedCurrencyLedger {
+    pub ledger: Ledger,
+    pub currencies: HashSet<FiatCurrency>,
+}
+
+/// Collects ledgers breaking the currency policy, which points at misrouted
+/// operations.
+#[cfg(feature = "std")]
+pub fn check_ledger_currencies(
+    txs: &[Transaction],
+    policy: CurrencyPolicy,
+) -> Vec<MixedCurrencyLedger> {
+    if policy == CurrencyPolicy::Mixed {
+        return vec![];
+    }
+
+    infer_ledger_currencies(txs)
+        .into_iter()
+        .filter(|(_, currencies)| currencies.len() > 1)
+        .map(|(ledger, currencies)| MixedCurrencyLedger { ledger, currencies })
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use claim::assert_err;
     use fake::{faker, Fake};
+    #[cfg(feature = "std")]
+    use rust_decimal_macros::dec;
 
     use super::*;
+    #[cfg(feature = "std")]
+    use crate::operation::{test::operation, InflowOperation, Operation, OperationKind};
 
     impl quickcheck::Arbitrary for Ledger {
         fn arbitrary(_g: &mut quickcheck::Gen) -> Self {
@@ -32,4 +243,113 @@ mod test {
             quickcheck::empty_shrinker()
         }
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ledger_receiving_two_currencies_is_flagged() {
+        let deposit = |ledger: &str, currency| {
+            let operation = Operation {
+                ledger: Ledger::new(ledger),
+                ..operation(
+                    OperationKind::Inflow(InflowOperation::Deposit),
+                    AssetId::Currency(currency),
+                    dec!(1),
+                )
+            };
+
+            Transaction::from_operations(vec![operation]).unwrap()
+        };
+
+        let txs = vec![
+            deposit("Cash:USD", FiatCurrency::USD),
+            deposit("Cash:USD", FiatCurrency::EUR),
+            deposit("Cash:EUR", FiatCurrency::EUR),
+        ];
+
+        assert_eq!(infer_ledger_currencies(&txs).len(), 2);
+        assert!(check_ledger_currencies(&txs, CurrencyPolicy::Mixed).is_empty());
+        assert_eq!(
+            check_ledger_currencies(&txs, CurrencyPolicy::SingleCurrency),
+            vec![MixedCurrencyLedger {
+                ledger: Ledger::new("Cash:USD"),
+                currencies: HashSet::from([FiatCurrency::USD, FiatCurrency::EUR]),
+            }]
+        );
+    }
+
+    #[test]
+    fn ledger_path_is_parsed_into_hierarchy() {
+        let ledger = "Assets:Brokerage:USD".parse::<Ledger>().unwrap();
+
+        assert_eq!(ledger.segments().collect::<Vec<_>>(), vec![
+            "Assets",
+            "Brokerage",
+            "USD"
+        ]);
+        assert_eq!(ledger.parent(), Some(Ledger::new("Assets:Brokerage")));
+        assert_eq!(Ledger::new("Assets").parent(), None);
+        assert!(ledger.is_descendant_of(&Ledger::new("Assets")));
+        assert!(ledger.is_descendant_of(&Ledger::new("Assets:Brokerage")));
+        assert!(!ledger.is_descendant_of(&ledger));
+        assert!(!ledger.is_descendant_of(&Ledger::new("Assets:Broker")));
+    }
+
+    #[test]
+    fn ledger_path_with_empty_segment_is_rejected() {
+        assert_err!("Assets::USD".parse::<Ledger>());
+        assert_err!("Assets:".parse::<Ledger>());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn child_balances_are_rolled_up_into_parents() {
+        let usd = AssetId::Currency(crate::asset::FiatCurrency::USD);
+        let eur = AssetId::Currency(crate::asset::FiatCurrency::EUR);
+
+        let balances = HashMap::from([
+            (
+                Ledger::new("Assets:Bank:Checking"),
+                HashMap::from([(usd.to_owned(), dec!(100))]),
+            ),
+            (
+                Ledger::new("Assets:Bank:Savings"),
+                HashMap::from([(usd.to_owned(), dec!(50)), (eur.to_owned(), dec!(20))]),
+            ),
+            (
+                Ledger::new("Cash"),
+                HashMap::from([(usd.to_owned(), dec!(5))]),
+            ),
+        ]);
+
+        let rolled_up = rollup_balances(&balances, 2).unwrap();
+
+        assert_eq!(rolled_up.len(), 3);
+        assert_eq!(rolled_up[&Ledger::new("Assets:Bank")][&usd], dec!(150));
+        assert_eq!(rolled_up[&Ledger::new("Assets:Bank")][&eur], dec!(20));
+        assert_eq!(rolled_up[&Ledger::new("Assets")][&usd], dec!(150));
+        assert_eq!(rolled_up[&Ledger::new("Cash")][&usd], dec!(5));
+
+        let leaves = rollup_balances(&balances, usize::MAX).unwrap();
+
+        assert_eq!(
+            leaves[&Ledger::new("Assets:Bank:Checking")][&usd],
+            dec!(100)
+        );
+        assert_eq!(leaves[&Ledger::new("Assets")][&usd], dec!(150));
+
+        assert!(rollup_balances(&balances, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn canonical_ledgers_ignore_whitespace_and_optionally_case() {
+        assert_eq!(
+            Ledger::canonical(" Assets :  Main  Brokerage "),
+            Ledger::new("Assets:Main Brokerage")
+        );
+        assert_ne!(Ledger::canonical(" ABC "), Ledger::canonical("abc"));
+        assert_eq!(
+            Ledger::canonical_with(" ABC ", CaseFolding::Insensitive),
+            Ledger::canonical_with("abc", CaseFolding::Insensitive)
+        );
+    }
 }
@@ -2,15 +2,45 @@
 //!
 //! `delfin` is a library of entities and processes to make processing certain
 //! financial transactions easy and accurate.
+//!
+//! The model (assets, ledgers and operations) builds under `no_std` with
+//! `alloc`; everything else needs the `std` feature, on by default.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
+pub mod accounting;
 pub mod asset;
+#[cfg(feature = "std")]
 pub mod assets_trading;
+#[cfg(feature = "io")]
 pub mod data_sources;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod journal;
 pub mod ledger;
 pub mod operation;
+#[cfg(feature = "std")]
+pub mod portfolio;
+#[cfg(feature = "std")]
+pub mod price;
+#[cfg(feature = "io")]
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod tax;
+#[cfg(feature = "std")]
 pub mod transaction;
+#[cfg(feature = "std")]
+pub mod transfer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use claim::assert_ok;
 
@@ -1,15 +1,29 @@
-/// Keeps information about a ledger which is a wrapper for transactions.
+#[cfg(feature = "std")]
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use core::{cmp::Ordering, fmt, str::FromStr};
 
-use std::str::FromStr;
-
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{asset::Asset, ledger::Ledger};
+use crate::{
+    asset::{Asset, Money},
+    ledger::Ledger,
+};
+#[cfg(feature = "std")]
+use crate::{
+    asset::{AssetId, FiatCurrency},
+    price::{convert, PriceProvider},
+};
 
 /// Describes the smallest possible financial primitive.
-#[derive(Clone, Debug)]
+///
+/// Operations are ordered by time, not by value: by `executed_at` first,
+/// then by `id` for operations executed at the same time, and by the other
+/// fields only so that the order agrees with equality.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Operation {
     pub id: OperationId,
     pub kind: OperationKind,
@@ -17,9 +31,342 @@ pub struct Operation {
     pub asset: Asset,
     pub value: Decimal,
     pub executed_at: DateTime<Utc>,
+    /// Human-readable note from the data source, useful for manual review.
+    pub description: Option<String>,
+    /// Operation type as found in the data source, kept to audit how the
+    /// operation was classified. Empty for operations created by hand.
+    pub source_type: Option<String>,
+    /// Price of a unit of the asset the data source converted the value at,
+    /// i.e. `0.82 EUR` for a dollar, when the source tells.
+    pub fx_rate: Option<Money>,
+    /// Ledger on the other side of a transfer between the user's own
+    /// ledgers, i.e. the savings account money was moved to.
+    pub counterparty: Option<Ledger>,
+}
+
+impl Operation {
+    pub fn builder() -> OperationBuilder {
+        OperationBuilder::default()
+    }
+
+    /// Value with the sign of the operation's direction, i.e. negative for
+    /// outflows.
+    pub fn signed_value(&self) -> Decimal {
+        match self.kind {
+            OperationKind::Inflow(_)
+            | OperationKind::Adjustment(AdjustmentOperation::Credit) => self.value,
+            OperationKind::Outflow(_)
+            | OperationKind::Adjustment(AdjustmentOperation::Debit) => -self.value,
+        }
+    }
+
+    /// Whether the operation and `other` are the two sides of one transfer:
+    /// the same amount of an asset leaving one ledger for the other.
+    pub fn is_transfer_counterpart(&self, other: &Operation) -> bool {
+        self.counterparty.as_ref() == Some(&other.ledger)
+            && other.counterparty.as_ref() == Some(&self.ledger)
+            && self.asset.id() == other.asset.id()
+            && self.value == other.value
+            && self.signed_value().is_sign_negative()
+                != other.signed_value().is_sign_negative()
+    }
+
+    /// Calendar date of the execution in the `tz` time zone.
+    ///
+    /// Unlike the other way round, converting an instant into local time is
+    /// never ambiguous, even during DST transitions.
+    pub fn executed_date<Tz: TimeZone>(&self, tz: &Tz) -> NaiveDate {
+        self.executed_at.with_timezone(tz).date_naive()
+    }
+
+    /// Value converted into the `target` currency at `executed_at`, or `None`
+    /// when the provider has no rate for that time.
+    ///
+    /// The rate captured from the data source (see [`Operation::fx_rate`]) is
+    /// preferred over the provider's.
+    #[cfg(feature = "std")]
+    pub fn value_in(
+        &self,
+        target: &FiatCurrency,
+        provider: &dyn PriceProvider,
+    ) -> Option<Money> {
+        if let Some(rate) = self
+            .fx_rate
+            .as_ref()
+            .filter(|rate| &rate.currency == target)
+        {
+            return Some(Money::new(self.value * rate.amount, target.to_owned()));
+        }
+
+        match self.asset.id() {
+            AssetId::Currency(currency) => convert(
+                &Money::new(self.value, currency.to_owned()),
+                target,
+                self.executed_at,
+                provider,
+            ),
+            asset => provider
+                .price(asset, target, self.executed_at)
+                .map(|price| Money::new(self.value * price, target.to_owned())),
+        }
+    }
+}
+
+/// Renders a line for logs and reports, i.e.
+/// `2021-01-01 Dividend +12.50 USD on Apple Inc (Ledger: Brokerage)`.
+///
+/// The value is given at the scale of the asset (see [`AssetId::scale`]) and
+/// in its identifier, so that amounts in the same currency always read the
+/// same, followed by the description when there's one.
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.signed_value().is_sign_negative() {
+            '-'
+        } else {
+            '+'
+        };
+
+        let asset = self.asset.id();
+
+        write!(
+            f,
+            "{} {} {}{:.*} {}",
+            self.executed_at.date_naive(),
+            self.kind,
+            sign,
+            asset.scale() as usize,
+            asset.round(self.value),
+            asset
+        )?;
+
+        if let Some(description) = &self.description {
+            write!(f, " on {}", description)?;
+        }
+
+        write!(f, " (Ledger: {})", self.ledger.name())
+    }
+}
+
+impl Ord for Operation {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.executed_at
+            .cmp(&other.executed_at)
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.kind.cmp(&other.kind))
+            .then_with(|| self.ledger.cmp(&other.ledger))
+            .then_with(|| self.asset.cmp(&other.asset))
+            .then_with(|| self.value.cmp(&other.value))
+            .then_with(|| self.description.cmp(&other.description))
+            .then_with(|| self.source_type.cmp(&other.source_type))
+            .then_with(|| self.fx_rate.cmp(&other.fx_rate))
+            .then_with(|| self.counterparty.cmp(&other.counterparty))
+    }
+}
+
+impl PartialOrd for Operation {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Builds an [`Operation`], checking at compile time that the required fields
+/// are set.
+///
+/// Each required field starts as `()` and takes its type once set, so `build`
+/// is only available when all of them are:
+///
+/// ```
+/// use chrono::Utc;
+/// use delfin::{
+///     asset::{Asset, AssetId, FiatCurrency},
+///     ledger::Ledger,
+///     operation::{InflowOperation, Operation, OperationKind},
+/// };
+/// use rust_decimal::Decimal;
+///
+/// let operation = Operation::builder()
+///     .id("OP1".parse().unwrap())
+///     .kind(OperationKind::Inflow(InflowOperation::Deposit))
+///     .ledger(Ledger::new("Brokerage"))
+///     .asset(Asset::new(AssetId::Currency(FiatCurrency::USD), "USD".into()))
+///     .value(Decimal::ONE_HUNDRED)
+///     .executed_at(Utc::now())
+///     .description("Deposit from bank account")
+///     .build();
+/// ```
+///
+/// Leaving one of them out doesn't compile:
+///
+/// ```compile_fail
+/// use delfin::{
+///     asset::{Asset, AssetId, FiatCurrency},
+///     ledger::Ledger,
+///     operation::{InflowOperation, Operation, OperationKind},
+/// };
+/// use rust_decimal::Decimal;
+///
+/// let operation = Operation::builder()
+///     .id("OP1".parse().unwrap())
+///     .kind(OperationKind::Inflow(InflowOperation::Deposit))
+///     .ledger(Ledger::new("Brokerage"))
+///     .asset(Asset::new(AssetId::Currency(FiatCurrency::USD), "USD".into()))
+///     .value(Decimal::ONE_HUNDRED)
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct OperationBuilder<Id = (), Kind = (), L = (), A = (), V = (), At = ()> {
+    id: Id,
+    kind: Kind,
+    ledger: L,
+    asset: A,
+    value: V,
+    executed_at: At,
+    description: Option<String>,
+    source_type: Option<String>,
+    fx_rate: Option<Money>,
+    counterparty: Option<Ledger>,
+}
+
+impl<Id, Kind, L, A, V, At> OperationBuilder<Id, Kind, L, A, V, At> {
+    pub fn id(self, id: OperationId) -> OperationBuilder<OperationId, Kind, L, A, V, At> {
+        OperationBuilder {
+            id,
+            kind: self.kind,
+            ledger: self.ledger,
+            asset: self.asset,
+            value: self.value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn kind(
+        self,
+        kind: OperationKind,
+    ) -> OperationBuilder<Id, OperationKind, L, A, V, At> {
+        OperationBuilder {
+            id: self.id,
+            kind,
+            ledger: self.ledger,
+            asset: self.asset,
+            value: self.value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn ledger(self, ledger: Ledger) -> OperationBuilder<Id, Kind, Ledger, A, V, At> {
+        OperationBuilder {
+            id: self.id,
+            kind: self.kind,
+            ledger,
+            asset: self.asset,
+            value: self.value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn asset(self, asset: Asset) -> OperationBuilder<Id, Kind, L, Asset, V, At> {
+        OperationBuilder {
+            id: self.id,
+            kind: self.kind,
+            ledger: self.ledger,
+            asset,
+            value: self.value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn value(self, value: Decimal) -> OperationBuilder<Id, Kind, L, A, Decimal, At> {
+        OperationBuilder {
+            id: self.id,
+            kind: self.kind,
+            ledger: self.ledger,
+            asset: self.asset,
+            value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn executed_at(
+        self,
+        executed_at: DateTime<Utc>,
+    ) -> OperationBuilder<Id, Kind, L, A, V, DateTime<Utc>> {
+        OperationBuilder {
+            id: self.id,
+            kind: self.kind,
+            ledger: self.ledger,
+            asset: self.asset,
+            value: self.value,
+            executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+
+        self
+    }
+
+    pub fn source_type(mut self, source_type: impl Into<String>) -> Self {
+        self.source_type = Some(source_type.into());
+
+        self
+    }
+
+    pub fn fx_rate(mut self, fx_rate: Money) -> Self {
+        self.fx_rate = Some(fx_rate);
+
+        self
+    }
+
+    pub fn counterparty(mut self, counterparty: Ledger) -> Self {
+        self.counterparty = Some(counterparty);
+
+        self
+    }
 }
 
-#[derive(Clone, Debug)]
+impl OperationBuilder<OperationId, OperationKind, Ledger, Asset, Decimal, DateTime<Utc>> {
+    pub fn build(self) -> Operation {
+        Operation {
+            id: self.id,
+            kind: self.kind,
+            ledger: self.ledger,
+            asset: self.asset,
+            value: self.value,
+            executed_at: self.executed_at,
+            description: self.description,
+            source_type: self.source_type,
+            fx_rate: self.fx_rate,
+            counterparty: self.counterparty,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct OperationId(String);
 
 #[derive(Debug, Error)]
@@ -36,37 +383,474 @@ impl FromStr for OperationId {
     }
 }
 
-#[derive(Clone, Debug)]
+impl fmt::Display for OperationId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum OperationKind {
     Inflow(InflowOperation),
     Outflow(OutflowOperation),
+    /// Correction of a balance made by the data source, which is neither
+    /// income nor expense.
+    Adjustment(AdjustmentOperation),
+}
+
+impl OperationKind {
+    /// Kind of an operation undoing this one, moving the value in the other
+    /// direction, i.e. a withdrawal for a deposit or a rebate for a fee.
+    ///
+    /// Income of any kind is undone by a cost, and expenses other than fees
+    /// and interest by income.
+    pub fn reversed(&self) -> OperationKind {
+        match self {
+            OperationKind::Inflow(kind) => OperationKind::Outflow(match kind {
+                InflowOperation::Deposit => OutflowOperation::Withdrawal,
+                InflowOperation::Interest => OutflowOperation::Interest,
+                InflowOperation::Rebate => OutflowOperation::Fee {
+                    category: FeeCategory::Commission,
+                },
+                InflowOperation::Sale => OutflowOperation::Purchase,
+                InflowOperation::Income
+                | InflowOperation::Dividend
+                | InflowOperation::Reward { .. } => OutflowOperation::Cost,
+            }),
+            OperationKind::Outflow(kind) => OperationKind::Inflow(match kind {
+                OutflowOperation::Withdrawal => InflowOperation::Deposit,
+                OutflowOperation::Interest => InflowOperation::Interest,
+                OutflowOperation::Fee { .. } => InflowOperation::Rebate,
+                OutflowOperation::Purchase => InflowOperation::Sale,
+                OutflowOperation::Cost | OutflowOperation::Donation => {
+                    InflowOperation::Income
+                }
+            }),
+            OperationKind::Adjustment(AdjustmentOperation::Credit) => {
+                OperationKind::Adjustment(AdjustmentOperation::Debit)
+            }
+            OperationKind::Adjustment(AdjustmentOperation::Debit) => {
+                OperationKind::Adjustment(AdjustmentOperation::Credit)
+            }
+        }
+    }
+}
+
+/// Renders the kind for people, i.e. `Fee (commission)`.
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OperationKind::Inflow(kind) => fmt::Display::fmt(kind, f),
+            OperationKind::Outflow(kind) => fmt::Display::fmt(kind, f),
+            OperationKind::Adjustment(kind) => write!(f, "Adjustment ({})", kind),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum InflowOperation {
     Deposit,
     Income,
     Dividend,
-    Reward,
+    Interest,
+    /// Refund of a fee, i.e. a commission rebate.
+    Rebate,
+    /// Asset received for taking part in a network or a promotion rather
+    /// than bought, i.e. tokens from staking. Income at receipt.
+    Reward {
+        kind: RewardKind,
+    },
+    /// Cash received for an asset sold, when the asset leg of the trade is
+    /// unknown.
+    Sale,
 }
 
-#[derive(Clone, Debug)]
+impl fmt::Display for InflowOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InflowOperation::Deposit => f.write_str("Deposit"),
+            InflowOperation::Income => f.write_str("Income"),
+            InflowOperation::Dividend => f.write_str("Dividend"),
+            InflowOperation::Interest => f.write_str("Interest"),
+            InflowOperation::Rebate => f.write_str("Rebate"),
+            InflowOperation::Reward { kind } => write!(f, "Reward ({})", kind),
+            InflowOperation::Sale => f.write_str("Sale"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum OutflowOperation {
     Withdrawal,
+    /// Expense other than a fee.
     Cost,
+    Fee {
+        category: FeeCategory,
+    },
     Interest,
     Donation,
+    /// Cash paid for an asset bought, when the asset leg of the trade is
+    /// unknown.
+    Purchase,
+}
+
+impl fmt::Display for OutflowOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutflowOperation::Withdrawal => f.write_str("Withdrawal"),
+            OutflowOperation::Cost => f.write_str("Cost"),
+            OutflowOperation::Fee { category } => write!(f, "Fee ({})", category),
+            OutflowOperation::Interest => f.write_str("Interest"),
+            OutflowOperation::Donation => f.write_str("Donation"),
+            OutflowOperation::Purchase => f.write_str("Purchase"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum AdjustmentOperation {
+    Credit,
+    Debit,
+}
+
+impl fmt::Display for AdjustmentOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AdjustmentOperation::Credit => "credit",
+            AdjustmentOperation::Debit => "debit",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum RewardKind {
+    Staking,
+    /// Tokens given away, i.e. to holders of another token.
+    Airdrop,
+    Mining,
+    /// Paid on tokens lent out.
+    Interest,
+}
+
+impl fmt::Display for RewardKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RewardKind::Staking => "staking",
+            RewardKind::Airdrop => "airdrop",
+            RewardKind::Mining => "mining",
+            RewardKind::Interest => "interest",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum FeeCategory {
+    /// Charged by a broker for executing a trade.
+    Commission,
+    Spread,
+    Custody,
+    Withdrawal,
+}
+
+impl fmt::Display for FeeCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FeeCategory::Commission => "commission",
+            FeeCategory::Spread => "spread",
+            FeeCategory::Custody => "custody",
+            FeeCategory::Withdrawal => "withdrawal",
+        })
+    }
+}
+
+/// Kind of an operation from the signed amount and the category a data
+/// source gives it, i.e. `-2.5` and `commission`.
+///
+/// Categories are matched ignoring case, with `_` and `-` read as spaces.
+/// The sign picks the direction, so a commission credited back is a rebate
+/// and a negative dividend (i.e. a reversal or a withholding) is a cost.
+/// Rewards of no particular kind (i.e. `reward`) are taken for staking ones.
+/// Amounts of categories not known here are deposits when positive and
+/// withdrawals otherwise.
+pub fn operation_kind_from(amount: Decimal, category: &str) -> OperationKind {
+    operation_kind_from_direction(amount > Decimal::ZERO, category)
+}
+
+/// Kind of an operation from its direction and category, for data sources
+/// telling the direction apart from the amount, see [`operation_kind_from`].
+pub fn operation_kind_from_direction(inflow: bool, category: &str) -> OperationKind {
+    let category = category.trim().to_lowercase().replace(['_', '-'], " ");
+
+    match (category.as_str(), inflow) {
+        ("commission" | "fee" | "rebate", true) => {
+            OperationKind::Inflow(InflowOperation::Rebate)
+        }
+        ("commission" | "fee" | "rebate", false) => {
+            OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            })
+        }
+        ("dividend", true) => OperationKind::Inflow(InflowOperation::Dividend),
+        ("dividend" | "tax" | "withholding tax" | "cost", false) => {
+            OperationKind::Outflow(OutflowOperation::Cost)
+        }
+        ("tax" | "withholding tax" | "cost" | "income", true) => {
+            OperationKind::Inflow(InflowOperation::Income)
+        }
+        ("interest", true) => OperationKind::Inflow(InflowOperation::Interest),
+        ("interest", false) => OperationKind::Outflow(OutflowOperation::Interest),
+        ("adjustment", true) => OperationKind::Adjustment(AdjustmentOperation::Credit),
+        ("adjustment", false) => OperationKind::Adjustment(AdjustmentOperation::Debit),
+        ("reward" | "staking" | "staking reward", true) => {
+            OperationKind::Inflow(InflowOperation::Reward {
+                kind: RewardKind::Staking,
+            })
+        }
+        ("airdrop", true) => OperationKind::Inflow(InflowOperation::Reward {
+            kind: RewardKind::Airdrop,
+        }),
+        ("mining" | "mining reward", true) => {
+            OperationKind::Inflow(InflowOperation::Reward {
+                kind: RewardKind::Mining,
+            })
+        }
+        ("lending interest", true) => OperationKind::Inflow(InflowOperation::Reward {
+            kind: RewardKind::Interest,
+        }),
+        ("donation", false) => OperationKind::Outflow(OutflowOperation::Donation),
+        (_, true) => OperationKind::Inflow(InflowOperation::Deposit),
+        (_, false) => OperationKind::Outflow(OutflowOperation::Withdrawal),
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod test {
-    use std::str::FromStr;
-
-    use chrono::Duration;
+    use chrono::{Duration, TimeZone};
     use fake::{faker, Fake};
     use quickcheck::Arbitrary;
+    use rust_decimal_macros::dec;
 
     use super::*;
+    use crate::asset::{AssetClass, AssetId, FiatCurrency, TokenId};
+    #[cfg(feature = "std")]
+    use crate::price::StaticPriceProvider;
+
+    /// Creates an operation on the `Brokerage` ledger executed at 2021-01-01.
+    pub(crate) fn operation(
+        kind: OperationKind,
+        asset: AssetId,
+        value: Decimal,
+    ) -> Operation {
+        Operation {
+            id: "OP1".parse().unwrap(),
+            kind,
+            ledger: Ledger::new("Brokerage"),
+            asset: Asset::new(asset, "".into()),
+            value,
+            executed_at: Utc.with_ymd_and_hms(2021, 1, 1, 10, 0, 0).unwrap(),
+            description: None,
+            source_type: None,
+            fx_rate: None,
+            counterparty: None,
+        }
+    }
+
+    #[test]
+    fn kind_follows_category_and_sign() {
+        use AdjustmentOperation::*;
+        use InflowOperation::*;
+        use OperationKind::{Adjustment, Inflow, Outflow};
+        use OutflowOperation::{Cost, Fee, Withdrawal};
+
+        let commission = || {
+            Outflow(Fee {
+                category: FeeCategory::Commission,
+            })
+        };
+
+        let cases = [
+            (dec!(-2.5), "COMMISSION", commission()),
+            (dec!(0.5), "commission", Inflow(Rebate)),
+            (dec!(-2.5), "fee", commission()),
+            (dec!(0.5), "Rebate", Inflow(Rebate)),
+            (dec!(-0.5), "REBATE", commission()),
+            (dec!(12.5), "DIVIDEND", Inflow(Dividend)),
+            (dec!(-12.5), "dividend", Outflow(Cost)),
+            (dec!(-1.88), "withholding-tax", Outflow(Cost)),
+            (dec!(1.88), "WITHHOLDING_TAX", Inflow(Income)),
+            (dec!(0.3), "interest", Inflow(Interest)),
+            (dec!(-0.3), "interest", Outflow(OutflowOperation::Interest)),
+            (dec!(1), "adjustment", Adjustment(Credit)),
+            (dec!(-1), "adjustment", Adjustment(Debit)),
+            (
+                dec!(5),
+                "reward",
+                Inflow(Reward {
+                    kind: RewardKind::Staking,
+                }),
+            ),
+            (
+                dec!(5),
+                "Staking Reward",
+                Inflow(Reward {
+                    kind: RewardKind::Staking,
+                }),
+            ),
+            (
+                dec!(5),
+                "AIRDROP",
+                Inflow(Reward {
+                    kind: RewardKind::Airdrop,
+                }),
+            ),
+            (
+                dec!(5),
+                "mining",
+                Inflow(Reward {
+                    kind: RewardKind::Mining,
+                }),
+            ),
+            (
+                dec!(5),
+                "lending_interest",
+                Inflow(Reward {
+                    kind: RewardKind::Interest,
+                }),
+            ),
+            (dec!(-5), "donation", Outflow(OutflowOperation::Donation)),
+            (dec!(100), "FUNDING/WITHDRAWAL", Inflow(Deposit)),
+            (dec!(-100), "FUNDING/WITHDRAWAL", Outflow(Withdrawal)),
+            (dec!(0), "", Outflow(Withdrawal)),
+        ];
+
+        for (amount, category, kind) in cases {
+            assert_eq!(
+                operation_kind_from(amount, category),
+                kind,
+                "{} {}",
+                amount,
+                category
+            );
+        }
+    }
+
+    #[test]
+    fn builder_sets_every_field() {
+        let executed_at = Utc.with_ymd_and_hms(2021, 1, 1, 10, 0, 0).unwrap();
+        let fx_rate = Money::new(dec!(0.82), FiatCurrency::EUR);
+
+        let built = Operation::builder()
+            .executed_at(executed_at)
+            .value(dec!(100))
+            .asset(Asset::new(AssetId::Currency(FiatCurrency::USD), "".into()))
+            .ledger(Ledger::new("Brokerage"))
+            .kind(OperationKind::Inflow(InflowOperation::Deposit))
+            .id("OP1".parse().unwrap())
+            .source_type("FUNDING/WITHDRAWAL")
+            .fx_rate(fx_rate.to_owned())
+            .build();
+
+        assert_eq!(built, Operation {
+            source_type: Some("FUNDING/WITHDRAWAL".into()),
+            fx_rate: Some(fx_rate),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(100),
+            )
+        });
+    }
+
+    #[test]
+    fn dividend_is_displayed_with_its_sign() {
+        let dividend = Operation {
+            description: Some("Apple Inc".into()),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Dividend),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(12.50),
+            )
+        };
+        let tax = operation(
+            OperationKind::Outflow(OutflowOperation::Cost),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(1.88),
+        );
+
+        assert_eq!(
+            dividend.to_string(),
+            "2021-01-01 Dividend +12.50 USD on Apple Inc (Ledger: Brokerage)"
+        );
+        assert_eq!(
+            tax.to_string(),
+            "2021-01-01 Cost -1.88 USD (Ledger: Brokerage)"
+        );
+    }
+
+    #[test]
+    fn value_is_displayed_at_the_scale_of_its_asset() {
+        let commission = operation(
+            OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            }),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(2.5),
+        );
+        let reward = operation(
+            OperationKind::Inflow(InflowOperation::Reward {
+                kind: RewardKind::Staking,
+            }),
+            AssetId::Token(TokenId("ETH".into())),
+            dec!(0.01),
+        );
+
+        assert_eq!(
+            commission.to_string(),
+            "2021-01-01 Fee (commission) -2.50 USD (Ledger: Brokerage)"
+        );
+        assert_eq!(
+            reward.to_string(),
+            "2021-01-01 Reward (staking) +0.01000000 ETH (Ledger: Brokerage)"
+        );
+        assert_eq!(
+            OperationKind::Adjustment(AdjustmentOperation::Credit).to_string(),
+            "Adjustment (credit)"
+        );
+    }
+
+    #[test]
+    fn transfer_is_paired_by_counterparties() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let withdrawal = Operation {
+            counterparty: Some(Ledger::new("Savings")),
+            ..operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                usd.to_owned(),
+                dec!(500),
+            )
+        };
+        let deposit = Operation {
+            id: "OP2".parse().unwrap(),
+            ledger: Ledger::new("Savings"),
+            counterparty: Some(Ledger::new("Brokerage")),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                usd,
+                dec!(500),
+            )
+        };
+
+        assert!(withdrawal.is_transfer_counterpart(&deposit));
+        assert!(deposit.is_transfer_counterpart(&withdrawal));
+        assert!(!withdrawal.is_transfer_counterpart(&Operation {
+            value: dec!(400),
+            ..deposit.to_owned()
+        }));
+        assert!(!withdrawal.is_transfer_counterpart(&Operation {
+            counterparty: None,
+            ..deposit
+        }));
+    }
 
     impl quickcheck::Arbitrary for OperationId {
         fn arbitrary(_g: &mut quickcheck::Gen) -> Self {
@@ -80,9 +864,19 @@ pub(crate) mod test {
 
     impl quickcheck::Arbitrary for InflowOperation {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            g.choose(&[Self::Deposit, Self::Dividend, Self::Income, Self::Reward])
-                .unwrap()
-                .to_owned()
+            g.choose(&[
+                Self::Deposit,
+                Self::Dividend,
+                Self::Income,
+                Self::Interest,
+                Self::Rebate,
+                Self::Reward {
+                    kind: RewardKind::Staking,
+                },
+                Self::Sale,
+            ])
+            .unwrap()
+            .to_owned()
         }
 
         fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
@@ -92,9 +886,37 @@ pub(crate) mod test {
 
     impl quickcheck::Arbitrary for OutflowOperation {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            g.choose(&[Self::Cost, Self::Donation, Self::Interest, Self::Withdrawal])
-                .unwrap()
-                .to_owned()
+            let fee = Self::Fee {
+                category: Arbitrary::arbitrary(g),
+            };
+
+            g.choose(&[
+                Self::Cost,
+                Self::Donation,
+                fee,
+                Self::Interest,
+                Self::Purchase,
+                Self::Withdrawal,
+            ])
+            .unwrap()
+            .to_owned()
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            quickcheck::empty_shrinker()
+        }
+    }
+
+    impl quickcheck::Arbitrary for FeeCategory {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            g.choose(&[
+                Self::Commission,
+                Self::Custody,
+                Self::Spread,
+                Self::Withdrawal,
+            ])
+            .unwrap()
+            .to_owned()
         }
 
         fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
@@ -104,12 +926,16 @@ pub(crate) mod test {
 
     impl quickcheck::Arbitrary for OperationKind {
         fn arbitrary(g: &mut quickcheck::Gen) -> Self {
-            let seed: u8 = g.choose(&[0, 1]).unwrap().to_owned();
+            let seed: u8 = g.choose(&[0, 1, 2]).unwrap().to_owned();
 
-            if seed == 0 {
-                Self::Inflow(Arbitrary::arbitrary(g))
-            } else {
-                Self::Outflow(Arbitrary::arbitrary(g))
+            match seed {
+                0 => Self::Inflow(Arbitrary::arbitrary(g)),
+                1 => Self::Outflow(Arbitrary::arbitrary(g)),
+                _ => Self::Adjustment(
+                    g.choose(&[AdjustmentOperation::Credit, AdjustmentOperation::Debit])
+                        .unwrap()
+                        .to_owned(),
+                ),
             }
         }
 
@@ -126,24 +952,29 @@ pub(crate) mod test {
                 .unwrap()
                 .to_owned();
 
-            let int_part: u16 = g
+            let asset: Asset = Arbitrary::arbitrary(g);
+
+            // values of securities and tokens are quantities: whole shares,
+            // and token amounts with up to 8 decimal places
+            let scale: u32 = match asset.class() {
+                AssetClass::Fiat => asset.id().scale(),
+                AssetClass::Security => 0,
+                AssetClass::Token => *g.choose(&(0 ..= 8).collect::<Vec<_>>()).unwrap(),
+            };
+
+            let int_part: i64 = g
                 .choose(&(0 .. 1_000).collect::<Vec<_>>())
                 .unwrap()
                 .to_owned();
-            let decimal_part: u16 = g
-                .choose(&(0 .. 100).collect::<Vec<_>>())
-                .unwrap()
-                .to_owned();
+            let decimal_part = i64::from(u32::arbitrary(g)) % 10_i64.pow(scale);
 
-            let value_str = format!("{}.{}", &int_part, &decimal_part);
-
-            let value: Decimal = Decimal::from_str(&value_str).unwrap_or_default();
+            let value = Decimal::new(int_part * 10_i64.pow(scale) + decimal_part, scale);
 
             Self {
                 id: Arbitrary::arbitrary(g),
                 kind: Arbitrary::arbitrary(g),
                 ledger: Arbitrary::arbitrary(g),
-                asset: Arbitrary::arbitrary(g),
+                asset,
                 executed_at: faker::chrono::en::DateTimeBetween(
                     Utc::now()
                         .checked_sub_signed(Duration::days(days_count))
@@ -152,11 +983,127 @@ pub(crate) mod test {
                 )
                 .fake(),
                 value,
+                description: bool::arbitrary(g)
+                    .then(|| faker::lorem::en::Sentence(3 .. 8).fake()),
+                source_type: None,
+                fx_rate: None,
+                counterparty: None,
             }
         }
 
+        /// Shrinks towards a deposit without description, with value halving
+        /// down to zero within the asset's scale.
         fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
-            quickcheck::empty_shrinker()
+            let minimal_kind = OperationKind::Inflow(InflowOperation::Deposit);
+            let mut shrunk = vec![];
+
+            if self.kind != minimal_kind {
+                shrunk.push(Self {
+                    kind: minimal_kind,
+                    ..self.to_owned()
+                });
+            }
+
+            if self.description.is_some() {
+                shrunk.push(Self {
+                    description: None,
+                    ..self.to_owned()
+                });
+            }
+
+            if !self.value.is_zero() {
+                shrunk.push(Self {
+                    value: Decimal::ZERO,
+                    ..self.to_owned()
+                });
+
+                let half = (self.value / Decimal::TWO).round_dp(self.asset.id().scale());
+
+                if !half.is_zero() && half != self.value {
+                    shrunk.push(Self {
+                        value: half,
+                        ..self.to_owned()
+                    });
+                }
+            }
+
+            Box::new(shrunk.into_iter())
         }
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn eur_value_is_converted_into_usd() {
+        let operation = operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::EUR),
+            dec!(100),
+        );
+        let mut provider = StaticPriceProvider::default();
+
+        assert_eq!(operation.value_in(&FiatCurrency::USD, &provider), None);
+
+        provider.insert(
+            AssetId::Currency(FiatCurrency::EUR),
+            FiatCurrency::USD,
+            operation.executed_at.date_naive(),
+            dec!(1.22),
+        );
+
+        assert_eq!(
+            operation.value_in(&FiatCurrency::USD, &provider),
+            Some(Money::new(dec!(122), FiatCurrency::USD))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn captured_rate_is_preferred_over_provider() {
+        let mut operation = operation(
+            OperationKind::Inflow(InflowOperation::Dividend),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(100),
+        );
+        let mut provider = StaticPriceProvider::default();
+        provider.insert(
+            AssetId::Currency(FiatCurrency::USD),
+            FiatCurrency::EUR,
+            operation.executed_at.date_naive(),
+            dec!(0.80),
+        );
+
+        operation.fx_rate = Some(Money::new(dec!(0.82), FiatCurrency::EUR));
+
+        assert_eq!(
+            operation.value_in(&FiatCurrency::EUR, &provider),
+            Some(Money::new(dec!(82), FiatCurrency::EUR))
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn generated_fiat_values_have_cents_at_most(operation: Operation) -> bool {
+        operation.asset.class() != AssetClass::Fiat || operation.value.scale() <= 2
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn sorted_operations_are_in_chronological_order(mut operations: Vec<Operation>) {
+        operations.sort();
+
+        assert!(operations
+            .windows(2)
+            .all(|pair| pair[0].executed_at <= pair[1].executed_at));
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn order_agrees_with_equality(a: Operation, b: Operation) -> bool {
+        let same_time_and_id = Operation {
+            id: a.id.to_owned(),
+            executed_at: a.executed_at,
+            ..b.to_owned()
+        };
+
+        [&a, &b, &same_time_and_id]
+            .iter()
+            .all(|other| (a.cmp(other) == Ordering::Equal) == (&a == *other))
+    }
 }
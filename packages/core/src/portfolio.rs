@@ -0,0 +1,491 @@
+//! A set of transactions with the views derived from them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use once_cell::unsync::OnceCell;
+use rust_decimal::Decimal;
+
+use crate::{
+    accounting::{match_fifo, AccountingError, RealizedLot, Trade},
+    asset::{add_checked, AssetClass, AssetId, SumOverflow},
+    ledger::Ledger,
+    operation::Operation,
+    transaction::Transaction,
+};
+
+/// Owns transactions and caches the views derived from them.
+///
+/// Views are computed on first access and dropped whenever a transaction is
+/// added.
+#[derive(Debug, Default)]
+pub struct Portfolio {
+    transactions: Vec<Transaction>,
+    holdings: OnceCell<Result<HashMap<AssetId, Decimal>, SumOverflow>>,
+    ledgers: OnceCell<BTreeSet<Ledger>>,
+    realized_gains: OnceCell<Result<Vec<RealizedLot>, AccountingError>>,
+}
+
+impl Portfolio {
+    pub fn new(transactions: Vec<Transaction>) -> Self {
+        Self {
+            transactions,
+            ..Default::default()
+        }
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn add_transaction(&mut self, tx: Transaction) -> &mut Self {
+        self.transactions.push(tx);
+
+        self.holdings.take();
+        self.ledgers.take();
+        self.realized_gains.take();
+
+        self
+    }
+
+    /// Net quantity of each asset across all transactions.
+    pub fn holdings(&self) -> Result<&HashMap<AssetId, Decimal>, SumOverflow> {
+        self.holdings
+            .get_or_init(|| holdings(&self.transactions, |_| true))
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Net quantity of each asset from operations executed up to (and
+    /// including) the given date.
+    pub fn holdings_at(
+        &self,
+        date: DateTime<Utc>,
+    ) -> Result<HashMap<AssetId, Decimal>, SumOverflow> {
+        holdings(&self.transactions, |executed_at| executed_at <= date)
+    }
+
+    pub fn ledgers(&self) -> &BTreeSet<Ledger> {
+        self.ledgers.get_or_init(|| {
+            self.transactions
+                .iter()
+                .flat_map(|tx| tx.ledgers().iter().cloned())
+                .collect()
+        })
+    }
+
+    /// Gains realized by FIFO matching of the trades in the portfolio.
+    pub fn realized_gains(&self) -> Result<&[RealizedLot], AccountingError> {
+        self.realized_gains
+            .get_or_init(|| {
+                let trades = self
+                    .transactions
+                    .iter()
+                    .filter_map(|tx| Trade::try_from_transaction(tx).transpose())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match_fifo(&trades)
+            })
+            .as_deref()
+            .map_err(Clone::clone)
+    }
+
+    /// Transactions started in the given calendar year (in UTC).
+    pub fn transactions_in_year(&self, year: i32) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|tx| tx.started_at().year() == year)
+            .collect()
+    }
+
+    pub fn summary(&self) -> Result<PortfolioSummary, SumOverflow> {
+        summarize(&self.transactions)
+    }
+
+    pub fn asset_index(&self) -> AssetIndex<'_> {
+        AssetIndex::new(&self.transactions)
+    }
+}
+
+/// Positions of transactions by the assets they involve, to find ones
+/// touching an asset without scanning all of them.
+#[derive(Debug)]
+pub struct AssetIndex<'a> {
+    transactions: &'a [Transaction],
+    positions: HashMap<AssetId, Vec<usize>>,
+}
+
+impl<'a> AssetIndex<'a> {
+    pub fn new(transactions: &'a [Transaction]) -> Self {
+        let mut positions: HashMap<AssetId, Vec<usize>> = HashMap::new();
+
+        for (position, tx) in transactions.iter().enumerate() {
+            for asset in tx.assets() {
+                positions.entry(asset).or_default().push(position);
+            }
+        }
+
+        Self {
+            transactions,
+            positions,
+        }
+    }
+
+    /// Transactions involving the asset, in their order in the slice.
+    pub fn transactions_for(
+        &self,
+        asset: &AssetId,
+    ) -> impl Iterator<Item = &'a Transaction> + '_ {
+        self.positions
+            .get(asset)
+            .into_iter()
+            .flatten()
+            .map(|&position| &self.transactions[position])
+    }
+}
+
+fn holdings(
+    transactions: &[Transaction],
+    include: impl Fn(DateTime<Utc>) -> bool,
+) -> Result<HashMap<AssetId, Decimal>, SumOverflow> {
+    let mut holdings: HashMap<AssetId, Decimal> = HashMap::new();
+
+    for operation in transactions.iter().flat_map(|tx| tx.operations()) {
+        if include(operation.executed_at) {
+            add_checked(
+                &mut holdings,
+                operation.asset.id(),
+                operation.signed_value(),
+            )?;
+        }
+    }
+
+    Ok(holdings
+        .into_iter()
+        .map(|(asset, quantity)| {
+            let quantity = asset.round(quantity);
+            (asset, quantity)
+        })
+        .collect())
+}
+
+/// Net quantity of each asset, updated transaction by transaction, i.e. as
+/// they stream in or get corrected, rather than recomputed from all of them.
+///
+/// Applying a transaction and then reverting it leaves the state as it was.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BalanceState {
+    balances: HashMap<AssetId, Decimal>,
+}
+
+impl BalanceState {
+    /// Balances of the assets held, assets whose balance came back to zero
+    /// left out.
+    pub fn balances(&self) -> &HashMap<AssetId, Decimal> {
+        &self.balances
+    }
+
+    pub fn balance(&self, asset: &AssetId) -> Decimal {
+        self.balances.get(asset).copied().unwrap_or_default()
+    }
+
+    /// Adds the operations of a transaction to the balances. On overflow the
+    /// state is left unchanged.
+    pub fn apply(&mut self, tx: &Transaction) -> Result<(), SumOverflow> {
+        self.update(tx, |op| op.signed_value())
+    }
+
+    /// Takes back a transaction applied before.
+    pub fn revert(&mut self, tx: &Transaction) -> Result<(), SumOverflow> {
+        self.update(tx, |op| -op.signed_value())
+    }
+
+    fn update(
+        &mut self,
+        tx: &Transaction,
+        amount: impl Fn(&Operation) -> Decimal,
+    ) -> Result<(), SumOverflow> {
+        let mut changes = HashMap::new();
+
+        for operation in tx.operations() {
+            let asset = operation.asset.id();
+
+            if !changes.contains_key(asset) {
+                changes.insert(asset.to_owned(), self.balance(asset));
+            }
+
+            add_checked(&mut changes, asset, amount(operation))?;
+        }
+
+        for (asset, balance) in changes {
+            if balance.is_zero() {
+                self.balances.remove(&asset);
+            } else {
+                self.balances.insert(asset, balance);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Overview of a set of transactions, i.e. of an imported file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PortfolioSummary {
+    pub transactions_count: usize,
+    /// Start of the earliest and end of the latest transaction, if any.
+    pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Sum of incoming amounts per currency.
+    pub inflows: HashMap<AssetId, Decimal>,
+    /// Sum of outgoing amounts per currency, as positive numbers.
+    pub outflows: HashMap<AssetId, Decimal>,
+    pub assets: HashSet<AssetId>,
+}
+
+/// Sums up the transactions, see [`PortfolioSummary`].
+pub fn summarize(txs: &[Transaction]) -> Result<PortfolioSummary, SumOverflow> {
+    let mut summary = PortfolioSummary {
+        transactions_count: txs.len(),
+        ..Default::default()
+    };
+
+    for tx in txs {
+        summary.date_range = Some(match summary.date_range {
+            Some((from, to)) => (from.min(tx.started_at()), to.max(tx.finished_at())),
+            None => (tx.started_at(), tx.finished_at()),
+        });
+
+        for operation in tx {
+            let asset = operation.asset.id();
+
+            summary.assets.insert(asset.to_owned());
+
+            if asset.class() != AssetClass::Fiat {
+                continue;
+            }
+
+            let value = operation.signed_value();
+            let totals = if value.is_sign_negative() {
+                &mut summary.outflows
+            } else {
+                &mut summary.inflows
+            };
+
+            add_checked(totals, asset, value.abs())?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Transactions by the date they started on in the `tz` time zone, in
+/// chronological order.
+///
+/// A transaction spanning midnight falls on the day it started.
+pub fn group_transactions_by_day<'a, Tz: TimeZone>(
+    txs: &'a [Transaction],
+    tz: &Tz,
+) -> BTreeMap<NaiveDate, Vec<&'a Transaction>> {
+    let mut days: BTreeMap<NaiveDate, Vec<&Transaction>> = BTreeMap::new();
+
+    for tx in txs {
+        days.entry(tx.started_at().with_timezone(tz).date_naive())
+            .or_default()
+            .push(tx);
+    }
+
+    days
+}
+
+impl fmt::Display for PortfolioSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Transactions: {}", self.transactions_count)?;
+
+        if let Some((from, to)) = self.date_range {
+            writeln!(f, "Period: {} - {}", from.to_rfc3339(), to.to_rfc3339())?;
+        }
+
+        writeln!(f, "Assets: {}", self.assets.len())?;
+
+        for (title, totals) in [("Inflows", &self.inflows), ("Outflows", &self.outflows)]
+        {
+            writeln!(f, "{}:", title)?;
+
+            let mut totals = totals
+                .iter()
+                .map(|(asset, total)| (asset.to_string(), asset.round(*total)))
+                .collect::<Vec<_>>();
+            totals.sort();
+
+            for (asset, total) in totals {
+                writeln!(f, "  {} {}", asset, total)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::Warsaw;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::FiatCurrency,
+        operation::{test::operation, InflowOperation, OperationKind, OutflowOperation},
+        transaction::TransactionBuilder,
+    };
+
+    fn usd(kind: OperationKind, value: Decimal, year: i32) -> Transaction {
+        let mut operation = operation(kind, AssetId::Currency(FiatCurrency::USD), value);
+        operation.executed_at = Utc.with_ymd_and_hms(year, 6, 1, 10, 0, 0).unwrap();
+
+        TransactionBuilder::default()
+            .add_operation(operation)
+            .build()
+            .unwrap()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn applying_and_reverting_a_transaction_is_a_no_op(
+        history: Vec<Transaction>,
+        tx: Transaction,
+    ) {
+        let mut state = BalanceState::default();
+
+        for tx in &history {
+            state.apply(tx).unwrap();
+        }
+
+        let original = state.to_owned();
+
+        state.apply(&tx).unwrap();
+        state.revert(&tx).unwrap();
+
+        assert_eq!(state, original);
+        assert_eq!(
+            state
+                .balances()
+                .iter()
+                .map(|(asset, balance)| (asset.to_owned(), asset.round(*balance)))
+                .collect::<HashMap<_, _>>(),
+            holdings(&history, |_| true)
+                .unwrap()
+                .into_iter()
+                .filter(|(_, quantity)| !quantity.is_zero())
+                .collect::<HashMap<_, _>>()
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn cached_holdings_agree_with_fresh_ones(transactions: Vec<Transaction>) {
+        let mut portfolio = Portfolio::default();
+
+        for tx in transactions {
+            // read the cache before each addition to exercise invalidation
+            let _ = portfolio.holdings();
+            portfolio.add_transaction(tx);
+        }
+
+        assert_eq!(
+            portfolio.holdings().unwrap(),
+            &holdings(portfolio.transactions(), |_| true).unwrap()
+        );
+    }
+
+    #[test]
+    fn holdings_are_invalidated_when_transaction_is_added() {
+        let usd_id = AssetId::Currency(FiatCurrency::USD);
+        let mut portfolio = Portfolio::new(vec![usd(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            dec!(100),
+            2020,
+        )]);
+
+        assert_eq!(portfolio.holdings().unwrap()[&usd_id], dec!(100));
+
+        portfolio.add_transaction(usd(
+            OperationKind::Outflow(OutflowOperation::Withdrawal),
+            dec!(40),
+            2021,
+        ));
+
+        assert_eq!(portfolio.holdings().unwrap()[&usd_id], dec!(60));
+        assert_eq!(
+            portfolio
+                .holdings_at(Utc.with_ymd_and_hms(2020, 12, 31, 0, 0, 0).unwrap())
+                .unwrap()[&usd_id],
+            dec!(100)
+        );
+        assert_eq!(portfolio.transactions_in_year(2021).len(), 1);
+        assert_eq!(portfolio.ledgers().len(), 1);
+        assert_eq!(portfolio.realized_gains(), Ok(&[][..]));
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn asset_index_agrees_with_linear_scan(transactions: Vec<Transaction>) {
+        let index = AssetIndex::new(&transactions);
+
+        for asset in transactions.iter().flat_map(|tx| tx.assets()) {
+            assert_eq!(
+                index.transactions_for(&asset).collect::<Vec<_>>(),
+                transactions
+                    .iter()
+                    .filter(|tx| tx.contains_asset(&asset))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn transactions_are_grouped_by_local_day() {
+        let at = |day, hour| {
+            let mut operation = operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(1),
+            );
+            operation.executed_at =
+                Utc.with_ymd_and_hms(2021, 1, day, hour, 0, 0).unwrap();
+
+            Transaction::from_operations(vec![operation]).unwrap()
+        };
+        // 23:00 UTC is already the next day in Warsaw
+        let txs = vec![at(1, 10), at(1, 23), at(2, 10)];
+
+        let days = group_transactions_by_day(&txs, &Warsaw);
+
+        assert_eq!(
+            days.into_iter()
+                .map(|(day, txs)| (day.day(), txs.len()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2)]
+        );
+    }
+
+    #[cfg(feature = "io")]
+    #[test]
+    fn demo_file_is_summarized() {
+        let records =
+            crate::data_sources::exante::read_csv_file("input/exante/demo.csv").unwrap();
+        let txs = crate::data_sources::exante::group_records_into_transactions(&records)
+            .unwrap();
+
+        let summary = summarize(&txs).unwrap();
+
+        assert_eq!(summary.transactions_count, 6);
+        assert_eq!(
+            summary.date_range,
+            Some((
+                Utc.with_ymd_and_hms(2021, 1, 4, 9, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2021, 4, 1, 10, 20, 0).unwrap()
+            ))
+        );
+        assert!(summary.to_string().starts_with("Transactions: 6\n"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Sources of asset prices and exchange rates.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::asset::{AssetId, FiatCurrency, Money};
+
+pub trait PriceProvider {
+    /// Price of a single unit of `asset` expressed in `currency` at the given
+    /// time, or `None` when the price is unknown.
+    fn price(
+        &self,
+        asset: &AssetId,
+        currency: &FiatCurrency,
+        at: DateTime<Utc>,
+    ) -> Option<Decimal>;
+}
+
+/// Converts money into the `target` currency using the rate at the given time.
+pub fn convert(
+    money: &Money,
+    target: &FiatCurrency,
+    at: DateTime<Utc>,
+    provider: &dyn PriceProvider,
+) -> Option<Money> {
+    if &money.currency == target {
+        return Some(money.to_owned());
+    }
+
+    let rate =
+        provider.price(&AssetId::Currency(money.currency.to_owned()), target, at)?;
+
+    Some(Money::new(money.amount * rate, target.to_owned()))
+}
+
+/// Provider answering from a fixed table of daily prices.
+///
+/// # Example
+/// ```
+/// use chrono::{NaiveDate, TimeZone, Utc};
+/// use delfin::{
+///     asset::{AssetId, FiatCurrency},
+///     price::{PriceProvider, StaticPriceProvider},
+/// };
+/// use rust_decimal_macros::dec;
+///
+/// let mut provider = StaticPriceProvider::default();
+/// provider.insert(
+///     AssetId::Currency(FiatCurrency::EUR),
+///     FiatCurrency::USD,
+///     NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+///     dec!(1.22),
+/// );
+///
+/// let at = Utc.with_ymd_and_hms(2021, 1, 4, 15, 0, 0).unwrap();
+/// let rate = provider.price(&AssetId::Currency(FiatCurrency::EUR), &FiatCurrency::USD, at);
+///
+/// assert_eq!(rate, Some(dec!(1.22)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StaticPriceProvider {
+    prices: HashMap<(AssetId, FiatCurrency, NaiveDate), Decimal>,
+}
+
+impl StaticPriceProvider {
+    pub fn insert(
+        &mut self,
+        asset: AssetId,
+        currency: FiatCurrency,
+        date: NaiveDate,
+        price: Decimal,
+    ) -> &mut Self {
+        self.prices.insert((asset, currency, date), price);
+
+        self
+    }
+}
+
+impl PriceProvider for StaticPriceProvider {
+    fn price(
+        &self,
+        asset: &AssetId,
+        currency: &FiatCurrency,
+        at: DateTime<Utc>,
+    ) -> Option<Decimal> {
+        self.prices
+            .get(&(asset.to_owned(), currency.to_owned(), at.date_naive()))
+            .copied()
+    }
+}
+
+/// Provider remembering the prices `P` gave, so that it's asked at most once
+/// per asset, currency and day, i.e. when it fetches them from the network.
+///
+/// Prices are kept by day, so any time of a day already asked for gets the
+/// price of the first one. Unknown prices are remembered too.
+#[derive(Debug, Default)]
+pub struct CachingPriceProvider<P> {
+    inner: P,
+    prices: Mutex<HashMap<(AssetId, FiatCurrency, NaiveDate), Option<Decimal>>>,
+}
+
+impl<P: PriceProvider> CachingPriceProvider<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            prices: Mutex::default(),
+        }
+    }
+
+    /// Forgets all prices, so that they're asked from `P` again.
+    pub fn clear(&self) {
+        self.prices.lock().unwrap().clear();
+    }
+}
+
+impl<P: PriceProvider> PriceProvider for CachingPriceProvider<P> {
+    fn price(
+        &self,
+        asset: &AssetId,
+        currency: &FiatCurrency,
+        at: DateTime<Utc>,
+    ) -> Option<Decimal> {
+        let key = (asset.to_owned(), currency.to_owned(), at.date_naive());
+
+        if let Some(price) = self.prices.lock().unwrap().get(&key) {
+            return *price;
+        }
+
+        // not holding the lock while the provider is asked
+        let price = self.inner.price(asset, currency, at);
+        self.prices.lock().unwrap().insert(key, price);
+
+        price
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingPriceProvider {
+        calls: Cell<usize>,
+    }
+
+    impl PriceProvider for CountingPriceProvider {
+        fn price(
+            &self,
+            _asset: &AssetId,
+            _currency: &FiatCurrency,
+            _at: DateTime<Utc>,
+        ) -> Option<Decimal> {
+            self.calls.set(self.calls.get() + 1);
+
+            Some(dec!(1.22))
+        }
+    }
+
+    #[test]
+    fn provider_is_asked_once_per_day() {
+        let provider = CachingPriceProvider::new(CountingPriceProvider::default());
+        let eur = AssetId::Currency(FiatCurrency::EUR);
+        let at = |day, hour| Utc.with_ymd_and_hms(2021, 1, day, hour, 0, 0).unwrap();
+
+        for hour in [9, 9, 15] {
+            assert_eq!(
+                provider.price(&eur, &FiatCurrency::USD, at(4, hour)),
+                Some(dec!(1.22))
+            );
+        }
+        assert_eq!(provider.inner.calls.get(), 1);
+
+        provider.price(&eur, &FiatCurrency::USD, at(5, 9));
+        provider.price(&eur, &FiatCurrency::JPY, at(5, 9));
+        assert_eq!(provider.inner.calls.get(), 3);
+
+        provider.clear();
+        provider.price(&eur, &FiatCurrency::USD, at(4, 9));
+        assert_eq!(provider.inner.calls.get(), 4);
+    }
+}
@@ -0,0 +1,347 @@
+//! Destinations for transactions produced while importing data.
+
+use std::{collections::HashSet, io::Write};
+
+use itertools::Itertools;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{operation::OperationId, transaction::Transaction};
+
+#[derive(Debug, Error)]
+pub enum SinkError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Accepts transactions one by one, as soon as they're produced.
+pub trait TransactionSink {
+    fn accept(&mut self, tx: Transaction) -> Result<(), SinkError>;
+
+    /// Flushes anything buffered, once no more transactions are coming.
+    fn finish(self) -> Result<(), SinkError>;
+}
+
+/// Collects transactions in memory.
+#[derive(Debug, Default)]
+pub struct VecSink {
+    transactions: Vec<Transaction>,
+}
+
+impl VecSink {
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        self.transactions
+    }
+}
+
+impl TransactionSink for VecSink {
+    fn accept(&mut self, tx: Transaction) -> Result<(), SinkError> {
+        self.transactions.push(tx);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/// Writes one CSV row per operation, with the `transaction` column holding
+/// the position of the operation's transaction in the output.
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+    transactions_count: usize,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(writer),
+            transactions_count: 0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OperationRow<'a> {
+    transaction: usize,
+    id: String,
+    kind: String,
+    ledger: &'a str,
+    asset: String,
+    asset_name: &'a str,
+    value: String,
+    executed_at: String,
+    description: Option<&'a str>,
+    /// Tags of the transaction, sorted and separated by `;`.
+    tags: &'a str,
+    /// Metadata of the transaction as `key=value` pairs, sorted by key and
+    /// separated by `;`.
+    metadata: &'a str,
+}
+
+impl<W: Write> TransactionSink for CsvSink<W> {
+    fn accept(&mut self, tx: Transaction) -> Result<(), SinkError> {
+        let tags = tx.tags.iter().map(String::as_str).sorted().join(";");
+        let metadata = tx
+            .metadata
+            .iter()
+            .sorted()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .join(";");
+
+        for operation in tx.operations() {
+            self.writer.serialize(OperationRow {
+                transaction: self.transactions_count,
+                id: operation.id.to_string(),
+                kind: operation.kind.to_string(),
+                ledger: operation.ledger.name(),
+                asset: operation.asset.id().to_string(),
+                asset_name: operation.asset.name(),
+                value: operation.value.to_string(),
+                executed_at: operation.executed_at.to_rfc3339(),
+                description: operation.description.as_deref(),
+                tags: &tags,
+                metadata: &metadata,
+            })?;
+        }
+
+        self.transactions_count += 1;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), SinkError> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Writes one JSON document per transaction per line.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> TransactionSink for JsonlSink<W> {
+    fn accept(&mut self, tx: Transaction) -> Result<(), SinkError> {
+        serde_json::to_writer(&mut self.writer, &tx)?;
+        self.writer.write_all(b"\n")?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), SinkError> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Passes transactions on to the inner sink without the operations it has
+/// already passed, i.e. when importing overlapping files.
+///
+/// Only ids of the operations are kept, so memory grows with the number of
+/// distinct operations rather than with the imported data. Transactions left
+/// without operations are dropped.
+pub struct DedupingSink<S: TransactionSink> {
+    inner: S,
+    seen: HashSet<OperationId>,
+}
+
+impl<S: TransactionSink> DedupingSink<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: TransactionSink> TransactionSink for DedupingSink<S> {
+    fn accept(&mut self, tx: Transaction) -> Result<(), SinkError> {
+        if tx
+            .iter()
+            .all(|operation| !self.seen.contains(&operation.id))
+        {
+            self.seen
+                .extend(tx.iter().map(|operation| operation.id.to_owned()));
+
+            return self.inner.accept(tx);
+        }
+
+        let operations = tx
+            .iter()
+            .filter(|operation| self.seen.insert(operation.id.to_owned()))
+            .cloned()
+            .collect();
+
+        match tx.with_operations(operations) {
+            Ok(tx) => self.inner.accept(tx),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn finish(self) -> Result<(), SinkError> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::{AssetId, FiatCurrency},
+        operation::{
+            test::operation, InflowOperation, Operation, OperationKind, OutflowOperation,
+        },
+        transaction::{reverse_transaction, TransactionBuilder, REVERSES_KEY},
+    };
+
+    fn transactions() -> Vec<Transaction> {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+
+        vec![
+            TransactionBuilder::default()
+                .add_operation(operation(
+                    OperationKind::Inflow(InflowOperation::Dividend),
+                    usd.to_owned(),
+                    dec!(2.05),
+                ))
+                .add_operation(operation(
+                    OperationKind::Outflow(OutflowOperation::Cost),
+                    usd.to_owned(),
+                    dec!(0.31),
+                ))
+                .build()
+                .unwrap(),
+            TransactionBuilder::default()
+                .add_operation(operation(
+                    OperationKind::Inflow(InflowOperation::Deposit),
+                    usd,
+                    dec!(100),
+                ))
+                .build()
+                .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn csv_sink_writes_a_row_per_operation() {
+        let mut output = vec![];
+        let mut sink = CsvSink::new(&mut output);
+
+        for tx in transactions() {
+            sink.accept(tx).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("transaction,id,kind"));
+        assert!(lines[2].starts_with("0,OP1,Cost,Brokerage,USD,,0.31,"));
+        assert!(lines[3].starts_with("1,OP1,Deposit,"));
+    }
+
+    #[test]
+    fn csv_sink_writes_metadata_sorted_by_key() {
+        let mut output = vec![];
+        let mut sink = CsvSink::new(&mut output);
+        let tx = transactions().remove(1);
+        let reversal = reverse_transaction(&tx, tx.started_at());
+
+        sink.accept(
+            TransactionBuilder::default()
+                .add_operation(tx.operations()[0].to_owned())
+                .meta("source", "exante")
+                .meta("batch", "7")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        sink.accept(reversal.to_owned()).unwrap();
+        sink.finish().unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert!(lines[0].ends_with(",tags,metadata"));
+        assert!(lines[1].ends_with(",,batch=7;source=exante"));
+        assert!(lines[2].ends_with(&format!(",,{}={}", REVERSES_KEY, tx.id())));
+    }
+
+    #[test]
+    fn jsonl_sink_round_trips_transactions() {
+        let mut output = vec![];
+        let mut sink = JsonlSink::new(&mut output);
+
+        for tx in transactions() {
+            sink.accept(tx).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let parsed = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Transaction>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(parsed, transactions());
+    }
+
+    #[test]
+    fn deduping_sink_passes_each_operation_once() {
+        let deposit = |id: &str| Operation {
+            id: id.parse().unwrap(),
+            ..operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(100),
+            )
+        };
+        let first_file = [vec!["1"], vec!["2", "3"]];
+        let second_file = [vec!["2", "3"], vec!["3", "4"], vec!["5"]];
+
+        let mut sink = DedupingSink::new(VecSink::default());
+
+        for ids in first_file.iter().chain(&second_file) {
+            let operations = ids.iter().map(|id| deposit(id)).collect();
+
+            sink.accept(Transaction::from_operations(operations).unwrap())
+                .unwrap();
+        }
+
+        let ids = sink
+            .into_inner()
+            .into_transactions()
+            .iter()
+            .map(|tx| tx.iter().map(|op| op.id.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, vec![vec!["1"], vec!["2", "3"], vec!["4"], vec!["5"]]);
+    }
+}
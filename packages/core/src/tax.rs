@@ -0,0 +1,459 @@
+//! Buckets transactions into the categories used in tax reports.
+
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    asset::{add_checked, AssetClass, AssetId, FiatCurrency, SumOverflow},
+    operation::{FeeCategory, InflowOperation, OperationKind, OutflowOperation},
+    transaction::Transaction,
+    transfer::TRANSFER_TAG,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TaxCategory {
+    CapitalGains,
+    DividendIncome,
+    InterestIncome,
+    OtherIncome,
+    Fees,
+    Donations,
+    NonTaxable,
+}
+
+/// Classifies a transaction by its dominant operation.
+///
+/// Operations dominate in the following order:
+/// 1. a leg in any non-fiat asset other than a reward, or cash from a sale,
+///    makes the transaction a trade, so [`TaxCategory::CapitalGains`] (commissions
+///    included), unless it's a
+///    dividend reinvestment (see [`Transaction::is_drip`]), which is
+///    [`TaxCategory::DividendIncome`],
+/// 2. a dividend makes it [`TaxCategory::DividendIncome`], even when the
+///    withholding tax is an outflow within the same transaction,
+/// 3. interest received makes it [`TaxCategory::InterestIncome`],
+/// 4. any other income or reward makes it [`TaxCategory::OtherIncome`],
+/// 5. fees (and their rebates), costs and interest paid make it
+///    [`TaxCategory::Fees`],
+/// 6. donations make it [`TaxCategory::Donations`].
+///
+/// Adjustments are excluded from classification, and transactions made of
+/// deposits, withdrawals and adjustments only are [`TaxCategory::NonTaxable`],
+/// as are transfers between the user's ledgers (see [`TRANSFER_TAG`]).
+pub fn classify_tax_category(tx: &Transaction) -> TaxCategory {
+    if tx.has_tag(TRANSFER_TAG) {
+        return TaxCategory::NonTaxable;
+    }
+
+    let operations = tx
+        .operations()
+        .iter()
+        .filter(|op| !matches!(op.kind, OperationKind::Adjustment(_)))
+        .collect::<Vec<_>>();
+
+    let has = |predicate: fn(&OperationKind) -> bool| {
+        operations.iter().any(|op| predicate(&op.kind))
+    };
+
+    if tx.is_drip() {
+        TaxCategory::DividendIncome
+    } else if operations.iter().any(|op| {
+        op.asset.class() != AssetClass::Fiat
+            && !matches!(
+                op.kind,
+                OperationKind::Inflow(InflowOperation::Reward { .. })
+            )
+    }) || has(|kind| {
+        matches!(kind, OperationKind::Inflow(InflowOperation::Sale))
+    }) {
+        TaxCategory::CapitalGains
+    } else if has(|kind| matches!(kind, OperationKind::Inflow(InflowOperation::Dividend)))
+    {
+        TaxCategory::DividendIncome
+    } else if has(|kind| matches!(kind, OperationKind::Inflow(InflowOperation::Interest)))
+    {
+        TaxCategory::InterestIncome
+    } else if has(|kind| {
+        matches!(
+            kind,
+            OperationKind::Inflow(
+                InflowOperation::Income | InflowOperation::Reward { .. }
+            )
+        )
+    }) {
+        TaxCategory::OtherIncome
+    } else if has(|kind| {
+        matches!(
+            kind,
+            OperationKind::Outflow(
+                OutflowOperation::Cost
+                    | OutflowOperation::Fee { .. }
+                    | OutflowOperation::Interest
+            ) | OperationKind::Inflow(InflowOperation::Rebate)
+        )
+    }) {
+        TaxCategory::Fees
+    } else if has(|kind| {
+        matches!(kind, OperationKind::Outflow(OutflowOperation::Donation))
+    }) {
+        TaxCategory::Donations
+    } else {
+        TaxCategory::NonTaxable
+    }
+}
+
+pub fn group_by_tax_category(
+    txs: &[Transaction],
+) -> HashMap<TaxCategory, Vec<&Transaction>> {
+    let mut groups: HashMap<TaxCategory, Vec<&Transaction>> = HashMap::new();
+
+    for tx in txs {
+        groups
+            .entry(classify_tax_category(tx))
+            .or_default()
+            .push(tx);
+    }
+
+    groups
+}
+
+/// Commissions paid in each currency by operations executed within `range`
+/// (start inclusive, end exclusive), i.e. charged in the currency of each
+/// trade.
+///
+/// Only fees of the [`FeeCategory::Commission`] category are counted, so
+/// that other costs (i.e. tax withheld from a dividend) don't add up. Legs in
+/// securities or tokens aren't counted, and rebates aren't deducted.
+pub fn commission_report(
+    txs: &[Transaction],
+    range: (DateTime<Utc>, DateTime<Utc>),
+) -> Result<HashMap<FiatCurrency, Decimal>, SumOverflow> {
+    let (start, end) = range;
+    let mut totals = HashMap::new();
+
+    for operation in txs.iter().flat_map(|tx| tx.iter()) {
+        let is_commission = operation.kind
+            == OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            });
+
+        if is_commission
+            && operation.asset.class() == AssetClass::Fiat
+            && (start .. end).contains(&operation.executed_at)
+        {
+            add_checked(&mut totals, operation.asset.id(), operation.value)?;
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .filter_map(|(asset, total)| match asset {
+            AssetId::Currency(currency) => Some((currency, total)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// First day of a fiscal year.
+///
+/// # Example
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use delfin::tax::{fiscal_year_range, FiscalYear};
+///
+/// let (from, to) = fiscal_year_range(2021, &FiscalYear::AU, &Utc).unwrap();
+///
+/// assert_eq!(from, Utc.with_ymd_and_hms(2021, 7, 1, 0, 0, 0).unwrap());
+/// assert_eq!(to, Utc.with_ymd_and_hms(2022, 7, 1, 0, 0, 0).unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FiscalYear {
+    pub start_month: u32,
+    pub start_day: u32,
+}
+
+impl FiscalYear {
+    /// January to December.
+    pub const CALENDAR: FiscalYear = FiscalYear {
+        start_month: 1,
+        start_day: 1,
+    };
+
+    /// July to June.
+    pub const AU: FiscalYear = FiscalYear {
+        start_month: 7,
+        start_day: 1,
+    };
+
+    /// April 6th to April 5th.
+    pub const UK: FiscalYear = FiscalYear {
+        start_month: 4,
+        start_day: 6,
+    };
+
+    fn start_date(&self, year: i32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, self.start_month, self.start_day)
+    }
+}
+
+impl Default for FiscalYear {
+    fn default() -> Self {
+        Self::CALENDAR
+    }
+}
+
+/// Bounds `[from, to)` of the fiscal year starting in the given calendar
+/// `year`, i.e. the UK fiscal year 2021 runs from April 6th, 2021 to April
+/// 5th, 2022. Both bounds are midnights in the `tz` time zone, resolved with
+/// [`local_to_utc`] when a DST transition happens at midnight.
+///
+/// Returns `None` when the fiscal year start is not a valid date.
+pub fn fiscal_year_range<Tz: TimeZone>(
+    year: i32,
+    config: &FiscalYear,
+    tz: &Tz,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let local_midnight =
+        |date: NaiveDate| Some(local_to_utc(tz, &date.and_hms_opt(0, 0, 0)?));
+
+    Some((
+        local_midnight(config.start_date(year)?)?,
+        local_midnight(config.start_date(year + 1)?)?,
+    ))
+}
+
+/// Instant of a local time in the `tz` time zone.
+///
+/// Local times repeated when clocks fall back resolve to the earlier instant.
+/// Local times skipped when clocks spring forward are shifted forward by the
+/// length of the gap, i.e. 02:30 in Warsaw on March 28th, 2021 is 03:30.
+pub fn local_to_utc<Tz: TimeZone>(tz: &Tz, local: &NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(local) {
+        LocalResult::Single(date) | LocalResult::Ambiguous(date, _) => {
+            date.with_timezone(&Utc)
+        }
+        LocalResult::None => {
+            // the offset in force before the gap, as gaps are hours apart
+            let offset = tz.offset_from_utc_datetime(&(*local - Duration::days(1)));
+
+            DateTime::from_naive_utc_and_offset(*local - offset.fix(), Utc)
+        }
+    }
+}
+
+/// Transactions started within the fiscal year, see [`fiscal_year_range`].
+pub fn transactions_in_fiscal_year<'a, Tz: TimeZone>(
+    txs: &'a [Transaction],
+    year: i32,
+    config: &FiscalYear,
+    tz: &Tz,
+) -> Vec<&'a Transaction> {
+    let Some((from, to)) = fiscal_year_range(year, config, tz) else {
+        return vec![];
+    };
+
+    txs.iter()
+        .filter(|tx| from <= tx.started_at() && tx.started_at() < to)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::{London, Warsaw};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::{AssetId, FiatCurrency, ISIN},
+        operation::{test::operation, Operation, RewardKind},
+        transaction::TransactionBuilder,
+    };
+
+    fn usd(kind: OperationKind) -> Operation {
+        operation(kind, AssetId::Currency(FiatCurrency::USD), dec!(10))
+    }
+
+    fn tx(operations: Vec<Operation>) -> Transaction {
+        let mut builder = TransactionBuilder::default();
+
+        for operation in operations {
+            builder.add_operation(operation);
+        }
+
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn transactions_are_classified_by_dominant_operation() {
+        let trade = tx(vec![
+            operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Security("US0378331005".parse::<ISIN>().unwrap()),
+                dec!(1),
+            ),
+            usd(OperationKind::Outflow(OutflowOperation::Withdrawal)),
+            usd(OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Commission,
+            })),
+        ]);
+        let dividend = tx(vec![
+            usd(OperationKind::Inflow(InflowOperation::Dividend)),
+            usd(OperationKind::Outflow(OutflowOperation::Cost)),
+        ]);
+        let interest = tx(vec![usd(OperationKind::Inflow(InflowOperation::Interest))]);
+        let reward = tx(vec![usd(OperationKind::Inflow(InflowOperation::Reward {
+            kind: RewardKind::Staking,
+        }))]);
+        let fee = tx(vec![usd(OperationKind::Outflow(OutflowOperation::Fee {
+            category: FeeCategory::Custody,
+        }))]);
+        let cost = tx(vec![usd(OperationKind::Outflow(OutflowOperation::Cost))]);
+        let donation = tx(vec![usd(OperationKind::Outflow(
+            OutflowOperation::Donation,
+        ))]);
+        let deposit = tx(vec![usd(OperationKind::Inflow(InflowOperation::Deposit))]);
+
+        assert_eq!(classify_tax_category(&trade), TaxCategory::CapitalGains);
+        assert_eq!(
+            classify_tax_category(&dividend),
+            TaxCategory::DividendIncome
+        );
+        assert_eq!(
+            classify_tax_category(&interest),
+            TaxCategory::InterestIncome
+        );
+        assert_eq!(classify_tax_category(&reward), TaxCategory::OtherIncome);
+        assert_eq!(classify_tax_category(&fee), TaxCategory::Fees);
+        assert_eq!(classify_tax_category(&cost), TaxCategory::Fees);
+        assert_eq!(classify_tax_category(&donation), TaxCategory::Donations);
+        assert_eq!(classify_tax_category(&deposit), TaxCategory::NonTaxable);
+    }
+
+    #[test]
+    fn transactions_are_grouped_by_category() {
+        let txs = vec![
+            tx(vec![usd(OperationKind::Inflow(InflowOperation::Dividend))]),
+            tx(vec![usd(OperationKind::Inflow(InflowOperation::Deposit))]),
+            tx(vec![usd(OperationKind::Inflow(InflowOperation::Dividend))]),
+        ];
+
+        let groups = group_by_tax_category(&txs);
+
+        assert_eq!(groups[&TaxCategory::DividendIncome].len(), 2);
+        assert_eq!(groups[&TaxCategory::NonTaxable].len(), 1);
+        assert!(!groups.contains_key(&TaxCategory::CapitalGains));
+    }
+
+    #[test]
+    fn commissions_are_totaled_per_currency() {
+        let commission = |currency, value, day| Operation {
+            executed_at: Utc.with_ymd_and_hms(2021, 1, day, 10, 0, 0).unwrap(),
+            ..operation(
+                OperationKind::Outflow(OutflowOperation::Fee {
+                    category: FeeCategory::Commission,
+                }),
+                AssetId::Currency(currency),
+                value,
+            )
+        };
+        let txs = vec![
+            tx(vec![commission(FiatCurrency::USD, dec!(1.5), 4)]),
+            tx(vec![commission(FiatCurrency::EUR, dec!(2), 5)]),
+            tx(vec![
+                usd(OperationKind::Outflow(OutflowOperation::Withdrawal)),
+                Operation {
+                    id: "OP2".parse().unwrap(),
+                    ..commission(FiatCurrency::USD, dec!(0.75), 6)
+                },
+            ]),
+            // tax withheld from a dividend and other fees aren't commissions
+            tx(vec![
+                usd(OperationKind::Inflow(InflowOperation::Dividend)),
+                usd(OperationKind::Outflow(OutflowOperation::Cost)),
+            ]),
+            tx(vec![usd(OperationKind::Outflow(OutflowOperation::Fee {
+                category: FeeCategory::Custody,
+            }))]),
+            // after the range
+            tx(vec![commission(FiatCurrency::EUR, dec!(3), 20)]),
+        ];
+        let range = (
+            Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2021, 1, 20, 0, 0, 0).unwrap(),
+        );
+
+        assert_eq!(
+            commission_report(&txs, range).unwrap(),
+            HashMap::from([
+                (FiatCurrency::USD, dec!(2.25)),
+                (FiatCurrency::EUR, dec!(2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn uk_fiscal_year_starts_on_april_sixth_local_time() {
+        let (from, to) = fiscal_year_range(2021, &FiscalYear::UK, &London).unwrap();
+
+        // British Summer Time is one hour ahead of UTC
+        assert_eq!(from, Utc.with_ymd_and_hms(2021, 4, 5, 23, 0, 0).unwrap());
+        assert_eq!(to, Utc.with_ymd_and_hms(2022, 4, 5, 23, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn transactions_are_filtered_by_fiscal_year() {
+        let at = |month, day, hour| {
+            let mut op = usd(OperationKind::Inflow(InflowOperation::Deposit));
+            op.executed_at = London
+                .with_ymd_and_hms(2021, month, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc);
+
+            tx(vec![op])
+        };
+
+        let txs = vec![at(4, 5, 23), at(4, 6, 0), at(4, 6, 12)];
+
+        let in_2020 = transactions_in_fiscal_year(&txs, 2020, &FiscalYear::UK, &London);
+        let in_2021 = transactions_in_fiscal_year(&txs, 2021, &FiscalYear::UK, &London);
+
+        assert_eq!(in_2020, vec![&txs[0]]);
+        assert_eq!(in_2021, vec![&txs[1], &txs[2]]);
+    }
+
+    #[test]
+    fn dst_transitions_resolve_deterministically() {
+        let local = |month, day| {
+            NaiveDate::from_ymd_opt(2021, month, day)
+                .unwrap()
+                .and_hms_opt(2, 30, 0)
+                .unwrap()
+        };
+
+        // 02:30 happens twice when clocks fall back on October 31st
+        assert_eq!(
+            local_to_utc(&Warsaw, &local(10, 31)),
+            Utc.with_ymd_and_hms(2021, 10, 31, 0, 30, 0).unwrap()
+        );
+        // and never when they spring forward on March 28th
+        assert_eq!(
+            local_to_utc(&Warsaw, &local(3, 28)),
+            Utc.with_ymd_and_hms(2021, 3, 28, 1, 30, 0).unwrap()
+        );
+
+        let mut op = usd(OperationKind::Inflow(InflowOperation::Deposit));
+
+        for hour in [0, 1] {
+            op.executed_at = Utc.with_ymd_and_hms(2021, 10, 31, hour, 30, 0).unwrap();
+
+            assert_eq!(
+                op.executed_date(&Warsaw),
+                NaiveDate::from_ymd_opt(2021, 10, 31).unwrap()
+            );
+        }
+    }
+}
@@ -1,89 +1,985 @@
-use std::{collections::HashSet, ops::Deref};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
 
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::{ledger::Ledger, operation::Operation};
+use crate::{
+    accounting::FxTrade,
+    asset::{add_checked, AssetClass, AssetId, SumOverflow},
+    filter::OperationFilter,
+    ledger::Ledger,
+    operation::{InflowOperation, Operation, OperationId, OperationKind},
+};
 
-#[derive(Clone, Debug)]
-pub struct Transaction {
-    pub operations: Vec<Operation>,
-    pub ledgers: HashSet<Ledger>,
-    pub started_at: DateTime<Utc>,
-    pub finished_at: DateTime<Utc>,
-}
+/// Metadata key of a reversing transaction holding the id of the
+/// transaction it reverses, see [`reverse_transaction`].
+pub const REVERSES_KEY: &str = "reverses";
 
-#[derive(Default, Debug)]
-pub struct TransactionBuilder {
+/// Deserialized through [`TransactionBuilder`], so that the time span and
+/// ledgers are derived from the operations rather than trusted.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "SerializedTransaction")]
+pub struct Transaction {
+    /// Kept private, as the time span and ledgers are derived from them, see
+    /// [`Transaction::with_operations`].
     operations: Vec<Operation>,
-    ledgers: HashSet<Ledger>,
-    started_at: Option<DateTime<Utc>>,
-    finished_at: Option<DateTime<Utc>>,
+    ledgers: BTreeSet<Ledger>,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    /// Labels attached by the user, i.e. `tax-exempt`.
+    #[serde(default)]
+    pub tags: HashSet<String>,
+    /// Notes attached by the user, by key.
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
 }
 
-impl TransactionBuilder {
-    pub fn add_operation(&mut self, operation: Operation) -> &mut Self {
-        let executed_at = operation.executed_at.to_owned();
+impl Transaction {
+    /// Operations of the transaction, which can't be changed in place.
+    ///
+    /// # Example
+    /// ```compile_fail
+    /// # use delfin::transaction::Transaction;
+    /// fn corrupt(tx: &mut Transaction) {
+    ///     tx.operations.clear();
+    /// }
+    /// ```
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
 
-        self.ledgers.insert(operation.ledger.to_owned());
+    /// Ledgers of the operations, ordered by name.
+    pub fn ledgers(&self) -> &BTreeSet<Ledger> {
+        &self.ledgers
+    }
+
+    /// Execution time of the earliest operation.
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Execution time of the latest operation.
+    pub fn finished_at(&self) -> DateTime<Utc> {
+        self.finished_at
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Operation> {
+        self.operations.iter()
+    }
+
+    /// Number of operations, at least one in a built transaction.
+    pub fn operation_count(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Number of distinct ledgers, at least one and at most as many as the
+    /// operations in a built transaction.
+    pub fn ledger_count(&self) -> usize {
+        self.ledgers.len()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn contains_asset(&self, id: &AssetId) -> bool {
+        self.operations.iter().any(|op| op.asset.id() == id)
+    }
+
+    /// Distinct assets involved in the transaction.
+    pub fn assets(&self) -> HashSet<AssetId> {
+        self.operations
+            .iter()
+            .map(|op| op.asset.id().to_owned())
+            .collect()
+    }
+
+    /// Creates a transaction spanning the given operations.
+    pub fn from_operations(
+        operations: Vec<Operation>,
+    ) -> Result<Transaction, TransactionBuildError> {
+        let mut tx_builder = TransactionBuilder::default();
+
+        for operation in operations {
+            tx_builder.add_operation(operation);
+        }
+
+        tx_builder.build()
+    }
 
-        if let Some(started_at) = self.started_at {
-            if executed_at < started_at {
-                self.started_at = Some(executed_at)
+    /// Transaction with the tags and metadata of this one, but made of other
+    /// operations, spanning and in the ledgers of those.
+    pub fn with_operations(
+        &self,
+        operations: Vec<Operation>,
+    ) -> Result<Transaction, TransactionBuildError> {
+        let mut tx_builder = TransactionBuilder {
+            tags: self.tags.to_owned(),
+            metadata: self.metadata.to_owned(),
+            ..Default::default()
+        };
+
+        for operation in operations {
+            tx_builder.add_operation(operation);
+        }
+
+        tx_builder.build()
+    }
+
+    /// Net value of the transaction per asset, rounded with
+    /// [`AssetId::round`].
+    pub fn net_value_by_asset(&self) -> Result<HashMap<AssetId, Decimal>, SumOverflow> {
+        let mut net_values: HashMap<AssetId, Decimal> = HashMap::new();
+
+        for operation in &self.operations {
+            add_checked(
+                &mut net_values,
+                operation.asset.id(),
+                operation.signed_value(),
+            )?;
+        }
+
+        Ok(net_values
+            .into_iter()
+            .map(|(asset, value)| {
+                let value = asset.round(value);
+                (asset, value)
+            })
+            .collect())
+    }
+
+    /// Identifies the transaction for deduplication.
+    ///
+    /// Only normalized fields go into the fingerprint, so that it survives
+    /// serialization: operations are taken in order of their ids, values
+    /// without trailing zeros and execution times to the second. Descriptions
+    /// and asset names are left out.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut operations = self.operations.iter().collect::<Vec<_>>();
+
+        operations.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let canonical = operations
+            .iter()
+            .map(|op| {
+                format!(
+                    "{}|{}|{}|{}|{}|{}",
+                    op.id,
+                    canonical_kind(&op.kind),
+                    op.ledger.name(),
+                    op.asset.id(),
+                    op.value.normalize(),
+                    op.executed_at.timestamp()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Fingerprint::of(canonical.as_bytes())
+    }
+
+    /// Identifies the transaction in reports, by its [`Transaction::fingerprint`].
+    pub fn id(&self) -> TransactionId {
+        self.fingerprint()
+    }
+
+    /// Whether the transaction reinvests a dividend (DRIP), i.e. a dividend
+    /// received together with an acquisition of a single non-fiat asset.
+    pub fn is_drip(&self) -> bool {
+        let mut acquisitions = self.operations.iter().filter(|op| {
+            matches!(op.kind, OperationKind::Inflow(_))
+                && op.asset.class() != AssetClass::Fiat
+        });
+
+        let has_dividend = self
+            .operations
+            .iter()
+            .any(|op| op.kind == OperationKind::Inflow(InflowOperation::Dividend));
+
+        has_dividend && acquisitions.next().is_some() && acquisitions.next().is_none()
+    }
+
+    /// The currency conversion made by the transaction, if that's what it
+    /// is, see [`FxTrade::from_transaction`].
+    pub fn as_fx_trade(&self) -> Option<FxTrade> {
+        FxTrade::from_transaction(self)
+    }
+
+    pub fn operations_matching<'a>(
+        &'a self,
+        filter: &'a OperationFilter,
+    ) -> impl Iterator<Item = &'a Operation> {
+        self.operations
+            .iter()
+            .filter(|operation| filter.matches(operation))
+    }
+
+    /// Whether every currency of the transaction nets to at most `tolerance`
+    /// either way, i.e. leaving a residual of fees rounding.
+    ///
+    /// A cheap check of one of the issues [`Transaction::validate`] reports.
+    /// Transactions netting beyond the range of [`Decimal`] aren't balanced.
+    pub fn is_balanced(&self, tolerance: Decimal) -> bool {
+        self.net_value_by_asset().is_ok_and(|net_values| {
+            net_values
+                .iter()
+                .filter(|(asset, _)| asset.class() == AssetClass::Fiat)
+                .all(|(_, net)| net.abs() <= tolerance)
+        })
+    }
+
+    /// Checks all invariants of the transaction, reporting every issue found
+    /// rather than the first one.
+    ///
+    /// Fiat amounts not netting to zero are reported too, as
+    /// [informational](TransactionIssue::is_informational) issues.
+    pub fn validate(&self) -> Vec<TransactionIssue> {
+        let mut issues = vec![];
+
+        if self.operations.is_empty() {
+            issues.push(TransactionIssue::MissingOperations);
+        }
+
+        if self.started_at > self.finished_at
+            || self.operations.iter().any(|op| {
+                op.executed_at < self.started_at || op.executed_at > self.finished_at
+            })
+        {
+            issues.push(TransactionIssue::DatesOutOfOrder);
+        }
+
+        let ledgers = self
+            .operations
+            .iter()
+            .map(|op| op.ledger.to_owned())
+            .collect::<BTreeSet<_>>();
+
+        if ledgers != self.ledgers {
+            issues.push(TransactionIssue::InconsistentLedgers);
+        }
+
+        let mut ids = HashSet::new();
+
+        for operation in &self.operations {
+            if !ids.insert(&operation.id) {
+                issues.push(TransactionIssue::DuplicateOperationId(
+                    operation.id.to_owned(),
+                ));
             }
         }
 
-        if let Some(finished_at) = self.finished_at {
-            if executed_at > finished_at {
-                self.finished_at = Some(executed_at)
+        match self.net_value_by_asset() {
+            Ok(net_values) => {
+                let mut unbalanced = net_values
+                    .into_iter()
+                    .filter(|(asset, net)| {
+                        asset.class() == AssetClass::Fiat && !net.is_zero()
+                    })
+                    .collect::<Vec<_>>();
+                unbalanced.sort_by_key(|(asset, _)| asset.to_string());
+
+                issues.extend(
+                    unbalanced
+                        .into_iter()
+                        .map(|(asset, net)| TransactionIssue::Unbalanced { asset, net }),
+                );
             }
+            Err(overflow) => issues.push(TransactionIssue::Overflow(overflow)),
         }
 
-        if self.started_at.is_none() && self.finished_at.is_none() {
-            self.started_at = Some(executed_at.to_owned());
-            self.finished_at = Some(executed_at);
+        issues
+    }
+}
+
+/// Kind of an operation as it goes into a [`Fingerprint`], spelled as
+/// fingerprints were first computed rather than for people, so that they
+/// don't change along with [`OperationKind`]'s `Display`.
+fn canonical_kind(kind: &OperationKind) -> String {
+    match kind {
+        OperationKind::Inflow(kind) => format!("{:?}", kind),
+        OperationKind::Outflow(kind) => format!("{:?}", kind),
+        OperationKind::Adjustment(kind) => format!("Adjustment{:?}", kind),
+    }
+}
+
+/// Issue found by [`Transaction::validate`].
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum TransactionIssue {
+    #[error("Transaction has no operations")]
+    MissingOperations,
+    #[error("Operations are executed outside of the transaction's time span")]
+    DatesOutOfOrder,
+    #[error("Ledgers of the transaction don't match ones of its operations")]
+    InconsistentLedgers,
+    #[error("Operation {0} appears more than once")]
+    DuplicateOperationId(OperationId),
+    #[error("{net} {asset} is left after netting the transaction")]
+    Unbalanced { asset: AssetId, net: Decimal },
+    #[error("{0}")]
+    Overflow(SumOverflow),
+}
+
+impl TransactionIssue {
+    /// Whether the issue is expected in some transactions, i.e. a deposit
+    /// doesn't net to zero.
+    pub fn is_informational(&self) -> bool {
+        matches!(self, TransactionIssue::Unbalanced { .. })
+    }
+}
+
+impl<'a> IntoIterator for &'a Transaction {
+    type Item = &'a Operation;
+    type IntoIter = std::slice::Iter<'a, Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Reference to a transaction, i.e. from a gain realized by it.
+pub type TransactionId = Fingerprint;
+
+/// FNV-1a hash of a transaction, stable across platforms and releases.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    pub(crate) fn of(bytes: &[u8]) -> Self {
+        Self(bytes.iter().fold(Self::OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(Self::PRIME)
+        }))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TransactionBuildError {
+    #[error("Missing operations")]
+    MissingOperations,
+
+    #[error("Missing ledgers")]
+    MissingLedgers,
+}
+
+/// Fields of a serialized [`Transaction`] it's built from, the derived ones
+/// are ignored.
+#[derive(Deserialize)]
+struct SerializedTransaction {
+    operations: Vec<Operation>,
+    #[serde(default)]
+    tags: HashSet<String>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+impl TryFrom<SerializedTransaction> for Transaction {
+    type Error = TransactionBuildError;
+
+    fn try_from(serialized: SerializedTransaction) -> Result<Self, Self::Error> {
+        let mut tx_builder = TransactionBuilder {
+            tags: serialized.tags,
+            metadata: serialized.metadata,
+            ..Default::default()
+        };
+
+        for operation in serialized.operations {
+            tx_builder.add_operation(operation);
         }
 
+        tx_builder.build()
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct TransactionBuilder {
+    operations: Vec<Operation>,
+    ledgers: BTreeSet<Ledger>,
+    tags: HashSet<String>,
+    metadata: HashMap<String, String>,
+}
+
+impl TransactionBuilder {
+    pub fn add_operation(&mut self, operation: Operation) -> &mut Self {
+        self.ledgers.insert(operation.ledger.to_owned());
         self.operations.push(operation);
 
         self
     }
 
-    pub fn build(&mut self) -> Result<Transaction, String> {
-        let Self {
-            operations,
-            ledgers,
+    pub fn tag(&mut self, tag: impl Into<String>) -> &mut Self {
+        self.tags.insert(tag.into());
+
+        self
+    }
+
+    pub fn meta(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &mut Self {
+        self.metadata.insert(key.into(), value.into());
+
+        self
+    }
+
+    /// Builds a transaction spanning from the earliest to the latest of its
+    /// operations.
+    pub fn build(&mut self) -> Result<Transaction, TransactionBuildError> {
+        let executed_at = self.operations.iter().map(|op| op.executed_at);
+
+        let (Some(started_at), Some(finished_at)) =
+            (executed_at.clone().min(), executed_at.max())
+        else {
+            return Err(TransactionBuildError::MissingOperations);
+        };
+
+        // holds as long as every operation adds its ledger, but consumers rely
+        // on it
+        if self.ledgers.is_empty() {
+            return Err(TransactionBuildError::MissingLedgers);
+        }
+
+        Ok(Transaction {
+            operations: self.operations.to_owned(),
+            ledgers: self.ledgers.to_owned(),
             started_at,
             finished_at,
-        } = self;
+            tags: self.tags.to_owned(),
+            metadata: self.metadata.to_owned(),
+        })
+    }
+}
 
-        if operations.is_empty() {
-            return Err("Missing operations".into());
-        }
+/// Transaction undoing `tx`, i.e. to correct an erroneous import, with
+/// every operation of `tx` moving its value in the other direction (see
+/// [`OperationKind::reversed`]) at `at`.
+///
+/// Operations keep their ledger and asset and get the id of the original one
+/// suffixed with `-REV`. The id of `tx` is kept in the
+/// [`REVERSES_KEY`] metadata.
+pub fn reverse_transaction(tx: &Transaction, at: DateTime<Utc>) -> Transaction {
+    let mut tx_builder = TransactionBuilder::default();
 
-        if let (Some(started_at), Some(finished_at)) = (started_at, finished_at) {
-            Ok(Transaction {
-                operations: self.operations.to_owned(),
-                ledgers: self.ledgers.to_owned(),
-                started_at: started_at.to_owned(),
-                finished_at: finished_at.to_owned(),
-            })
-        } else {
-            Err("Missing dates".into())
-        }
+    for operation in tx {
+        tx_builder.add_operation(Operation {
+            // parsing an operation id never fails
+            id: format!("{}-REV", operation.id).parse().unwrap(),
+            kind: operation.kind.reversed(),
+            executed_at: at,
+            source_type: None,
+            ..operation.to_owned()
+        });
     }
+
+    tx_builder
+        .meta(REVERSES_KEY, tx.id().to_string())
+        .build()
+        // built from the operations of a built transaction
+        .unwrap()
 }
 
 #[cfg(test)]
 mod tests {
-    use claim::{assert_err, assert_ok};
+    use std::sync::Mutex;
+
+    use chrono::TimeZone;
+    use claim::{assert_err, assert_matches, assert_ok};
+    use quickcheck::{Arbitrary, TestResult};
+    use rust_decimal_macros::dec;
 
     use super::*;
+    use crate::{
+        asset::{FiatCurrency, TokenId},
+        operation::{test, InflowOperation, OperationKind, OutflowOperation},
+    };
+
+    impl quickcheck::Arbitrary for Transaction {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let operations_count = *g.choose(&[1, 2, 3, 4]).unwrap();
+            let mut tx_builder = TransactionBuilder::default();
+
+            for _ in 0 .. operations_count {
+                tx_builder.add_operation(Arbitrary::arbitrary(g));
+            }
+
+            tx_builder.build().unwrap()
+        }
+
+        /// Shrinks towards fewer operations first, then shrinks each of the
+        /// remaining operations.
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let build = |operations: Vec<Operation>| {
+                let mut tx_builder = TransactionBuilder::default();
+
+                for operation in operations {
+                    tx_builder.add_operation(operation);
+                }
+
+                tx_builder.build().ok()
+            };
+
+            let operations = self.operations.to_owned();
+            let operations_count = operations.len();
+
+            let fewer_operations = (0 .. operations_count)
+                .filter(move |_| operations_count > 1)
+                .map({
+                    let operations = operations.to_owned();
+
+                    move |i| {
+                        let mut operations = operations.to_owned();
+                        operations.remove(i);
+                        operations
+                    }
+                });
+
+            let shrunk_operations = (0 .. operations_count).flat_map(move |i| {
+                let operations = operations.to_owned();
+
+                operations[i].shrink().map(move |operation| {
+                    let mut operations = operations.to_owned();
+                    operations[i] = operation;
+                    operations
+                })
+            });
+
+            Box::new(fewer_operations.chain(shrunk_operations).filter_map(build))
+        }
+    }
+
+    #[test]
+    fn failing_property_is_shrunk_to_a_small_transaction() {
+        static SMALLEST_FAILURE: Mutex<Option<Transaction>> = Mutex::new(None);
+
+        fn fails_on_multiple_operations(tx: Transaction) -> bool {
+            let passes = tx.operations.len() < 2;
+
+            if !passes {
+                *SMALLEST_FAILURE.lock().unwrap() = Some(tx);
+            }
+
+            passes
+        }
+
+        let result = quickcheck::QuickCheck::new()
+            .quicktest(fails_on_multiple_operations as fn(Transaction) -> bool);
+
+        assert_err!(result);
+
+        let smallest_failure = SMALLEST_FAILURE.lock().unwrap().take().unwrap();
+
+        assert_eq!(smallest_failure.operations.len(), 2);
+        assert!(smallest_failure.operations.iter().all(|op| {
+            op.value.is_zero()
+                && op.description.is_none()
+                && op.kind == OperationKind::Inflow(InflowOperation::Deposit)
+        }));
+    }
+
+    #[test]
+    fn transactions_are_filtered_by_tag() {
+        let deposit = test::operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(100),
+        );
+        let transactions = [
+            TransactionBuilder::default()
+                .add_operation(deposit.to_owned())
+                .tag("business")
+                .meta("invoice", "2021/01")
+                .build()
+                .unwrap(),
+            Transaction::from_operations(vec![deposit]).unwrap(),
+        ];
+
+        let tagged = transactions
+            .iter()
+            .filter(|tx| tx.has_tag("business"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].metadata["invoice"], "2021/01");
+        assert!(!tagged[0].has_tag("tax-exempt"));
+    }
 
     #[test]
     fn builder_returns_error_when_no_operations_provided() {
         let tx = TransactionBuilder::default().build();
 
-        assert_err!(tx);
+        assert_eq!(assert_err!(tx), TransactionBuildError::MissingOperations);
+    }
+
+    #[test]
+    fn net_value_overflow_is_an_error() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let deposit = OperationKind::Inflow(InflowOperation::Deposit);
+        let tx = Transaction::from_operations(vec![
+            test::operation(deposit.to_owned(), usd.to_owned(), Decimal::MAX),
+            test::operation(deposit, usd.to_owned(), Decimal::MAX),
+        ])
+        .unwrap();
+
+        assert_eq!(tx.net_value_by_asset(), Err(SumOverflow { asset: usd }));
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn iterating_transaction_yields_its_operations(tx: Transaction) {
+        let mut count = 0;
+
+        for operation in &tx {
+            assert!(tx.operations.contains(operation));
+            count += 1;
+        }
+
+        assert_eq!(count, tx.operations.len());
+        assert_eq!(tx.iter().count(), tx.operations.len());
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn fingerprint_survives_json_round_trip(tx: Transaction) {
+        let json = serde_json::to_string(&tx).unwrap();
+        let deserialized: Transaction = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized.fingerprint(), tx.fingerprint());
+    }
+
+    #[test]
+    fn deserialized_span_and_ledgers_are_derived_from_operations() {
+        let tx = Transaction::from_operations(vec![test::operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(100),
+        )])
+        .unwrap();
+        let mut json = serde_json::to_value(&tx).unwrap();
+
+        json["started_at"] = "2000-01-01T00:00:00Z".into();
+        json["ledgers"] = serde_json::json!(["Savings"]);
+
+        assert_eq!(serde_json::from_value::<Transaction>(json).unwrap(), tx);
+        assert_err!(serde_json::from_str::<Transaction>(r#"{"operations": []}"#));
+    }
+
+    #[test]
+    fn fingerprint_ignores_value_scale_and_operation_order() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let deposit = OperationKind::Inflow(InflowOperation::Deposit);
+        let first = test::operation(deposit.to_owned(), usd.to_owned(), dec!(10.50));
+        let second = Operation {
+            id: "OP2".parse().unwrap(),
+            ..test::operation(deposit, usd, dec!(2))
+        };
+        let rescaled = Operation {
+            value: dec!(10.5000),
+            ..first.to_owned()
+        };
+
+        let tx = Transaction::from_operations(vec![first, second.to_owned()]).unwrap();
+        let reordered = Transaction::from_operations(vec![second, rescaled]).unwrap();
+
+        assert_eq!(tx.fingerprint(), reordered.fingerprint());
+    }
+
+    #[test]
+    fn validation_reports_all_issues() {
+        let deposit = test::operation(
+            OperationKind::Inflow(InflowOperation::Deposit),
+            AssetId::Currency(FiatCurrency::USD),
+            dec!(100),
+        );
+        let withdrawal = Operation {
+            id: "OP2".parse().unwrap(),
+            ..test::operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(100),
+            )
+        };
+        let mut tx = Transaction::from_operations(vec![deposit, withdrawal]).unwrap();
+
+        assert_eq!(tx.validate(), vec![]);
+
+        tx.ledgers.insert(Ledger::new("Savings"));
+        tx.operations[1].id = "OP1".parse().unwrap();
+
+        assert_eq!(tx.validate(), vec![
+            TransactionIssue::InconsistentLedgers,
+            TransactionIssue::DuplicateOperationId("OP1".parse().unwrap()),
+        ]);
+    }
+
+    #[test]
+    fn ledgers_are_ordered_by_name() {
+        let ledger_names = ["Savings", "Brokerage", "Assets:Cash", "Brokerage:USD"];
+        let operations = ledger_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Operation {
+                id: format!("OP{}", i).parse().unwrap(),
+                ledger: Ledger::new(name),
+                ..test::operation(
+                    OperationKind::Inflow(InflowOperation::Deposit),
+                    AssetId::Currency(FiatCurrency::USD),
+                    dec!(100),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut reversed = operations.to_owned();
+        reversed.reverse();
+
+        let tx = Transaction::from_operations(operations).unwrap();
+        let names = tx.ledgers.iter().map(Ledger::name).collect::<Vec<_>>();
+
+        assert_eq!(names, vec![
+            "Assets:Cash",
+            "Brokerage",
+            "Brokerage:USD",
+            "Savings"
+        ]);
+        assert_eq!(
+            serde_json::to_value(&tx).unwrap()["ledgers"],
+            serde_json::to_value(Transaction::from_operations(reversed).unwrap())
+                .unwrap()["ledgers"]
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn built_transaction_has_ledgers(operations: Vec<Operation>) -> TestResult {
+        let Ok(tx) = Transaction::from_operations(operations) else {
+            return TestResult::discard();
+        };
+
+        TestResult::from_bool(
+            tx.ledger_count() >= 1 && tx.ledger_count() <= tx.operation_count(),
+        )
+    }
+
+    #[test]
+    fn transaction_is_created_from_operations_in_any_order() {
+        let at = |hour| Utc.with_ymd_and_hms(2021, 1, 1, hour, 0, 0).unwrap();
+        let deposit = |id: &str, ledger, hour| Operation {
+            id: id.parse().unwrap(),
+            ledger: Ledger::new(ledger),
+            executed_at: at(hour),
+            ..test::operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                AssetId::Currency(FiatCurrency::USD),
+                dec!(100),
+            )
+        };
+        let operations = vec![
+            deposit("OP2", "Savings", 12),
+            deposit("OP1", "Brokerage", 9),
+        ];
+
+        let tx = Transaction::from_operations(operations.to_owned()).unwrap();
+
+        assert_eq!(tx.operations(), operations.as_slice());
+        assert_eq!((tx.started_at(), tx.finished_at()), (at(9), at(12)));
+        assert_eq!(
+            tx.ledgers(),
+            &BTreeSet::from([Ledger::new("Brokerage"), Ledger::new("Savings")])
+        );
+        assert_eq!(
+            Transaction::from_operations(vec![]),
+            Err(TransactionBuildError::MissingOperations)
+        );
+    }
+
+    #[test]
+    fn transaction_lists_its_assets() {
+        let aapl = AssetId::Security("US0378331005".parse().unwrap());
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let eur = AssetId::Currency(FiatCurrency::EUR);
+
+        let tx = TransactionBuilder::default()
+            .add_operation(test::operation(
+                OperationKind::Inflow(InflowOperation::Deposit),
+                aapl.to_owned(),
+                dec!(10),
+            ))
+            .add_operation(test::operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                usd.to_owned(),
+                dec!(1500),
+            ))
+            .add_operation(test::operation(
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                usd.to_owned(),
+                dec!(2),
+            ))
+            .build()
+            .unwrap();
+
+        assert!(tx.contains_asset(&aapl));
+        assert!(tx.contains_asset(&usd));
+        assert!(!tx.contains_asset(&eur));
+        assert_eq!(tx.assets(), HashSet::from([aapl, usd]));
+    }
+
+    #[test]
+    fn other_operations_recompute_span_and_ledgers() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let deposit = OperationKind::Inflow(InflowOperation::Deposit);
+        let tx = TransactionBuilder::default()
+            .add_operation(test::operation(deposit.to_owned(), usd.to_owned(), dec!(1)))
+            .tag("tax-exempt")
+            .build()
+            .unwrap();
+        let later = Operation {
+            id: "OP2".parse().unwrap(),
+            ledger: Ledger::new("Savings"),
+            executed_at: Utc.with_ymd_and_hms(2021, 2, 1, 10, 0, 0).unwrap(),
+            ..test::operation(deposit, usd, dec!(2))
+        };
+
+        let other = tx.with_operations(vec![later.to_owned()]).unwrap();
+
+        assert_eq!(tx.operations().len(), 1);
+        assert_eq!(other.operations(), &[later.to_owned()]);
+        assert_eq!(
+            (other.started_at, other.finished_at),
+            (later.executed_at, later.executed_at)
+        );
+        assert_eq!(other.ledgers, BTreeSet::from([later.ledger]));
+        assert!(other.has_tag("tax-exempt"));
+        assert_matches!(
+            tx.with_operations(vec![]),
+            Err(TransactionBuildError::MissingOperations)
+        );
+    }
+
+    #[test]
+    fn balance_is_checked_per_currency() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let eur = AssetId::Currency(FiatCurrency::EUR);
+        let tx = |legs: &[(AssetId, Decimal)]| {
+            let mut tx_builder = TransactionBuilder::default();
+
+            for (i, (asset, value)) in legs.iter().enumerate() {
+                let kind = if value.is_sign_negative() {
+                    OperationKind::Outflow(OutflowOperation::Withdrawal)
+                } else {
+                    OperationKind::Inflow(InflowOperation::Deposit)
+                };
+
+                tx_builder.add_operation(Operation {
+                    id: format!("OP{}", i + 1).parse().unwrap(),
+                    ..test::operation(kind, asset.to_owned(), value.abs())
+                });
+            }
+
+            tx_builder.build().unwrap()
+        };
+
+        let balanced = tx(&[
+            (usd.to_owned(), dec!(100)),
+            (usd.to_owned(), dec!(-100)),
+            (AssetId::Token(TokenId("BTC".into())), dec!(0.01)),
+        ]);
+        let residual = tx(&[(usd.to_owned(), dec!(100)), (usd.to_owned(), dec!(-99.99))]);
+        let unbalanced = tx(&[(usd, dec!(100)), (eur, dec!(-85))]);
+
+        assert!(balanced.is_balanced(Decimal::ZERO));
+        assert!(!residual.is_balanced(Decimal::ZERO));
+        assert!(residual.is_balanced(dec!(0.01)));
+        assert!(!unbalanced.is_balanced(dec!(0.01)));
+    }
+
+    #[test]
+    fn reversed_transaction_negates_net_values() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let btc = AssetId::Token(TokenId("BTC".into()));
+        let tx = TransactionBuilder::default()
+            .add_operation(test::operation(
+                OperationKind::Outflow(OutflowOperation::Purchase),
+                usd.to_owned(),
+                dec!(1000),
+            ))
+            .add_operation(Operation {
+                id: "OP2".parse().unwrap(),
+                ..test::operation(
+                    OperationKind::Inflow(InflowOperation::Deposit),
+                    btc.to_owned(),
+                    dec!(0.05),
+                )
+            })
+            .build()
+            .unwrap();
+        let at = Utc.with_ymd_and_hms(2021, 2, 1, 12, 0, 0).unwrap();
+
+        let reversed = reverse_transaction(&tx, at);
+
+        let negated = tx
+            .net_value_by_asset()
+            .unwrap()
+            .into_iter()
+            .map(|(asset, value)| (asset, -value))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(reversed.net_value_by_asset().unwrap(), negated);
+        assert_eq!(
+            reversed.operations[0].kind,
+            OperationKind::Inflow(InflowOperation::Sale)
+        );
+        assert_eq!(reversed.operations[1].id.to_string(), "OP2-REV");
+        assert_eq!((reversed.started_at, reversed.finished_at), (at, at));
+        assert_eq!(reversed.metadata[REVERSES_KEY], tx.id().to_string());
+    }
+
+    #[test]
+    fn net_value_by_asset_is_rounded_to_asset_scale() {
+        let usd = AssetId::Currency(FiatCurrency::USD);
+        let btc = AssetId::Token(TokenId("BTC".into()));
+        let deposit = OperationKind::Inflow(InflowOperation::Deposit);
+
+        let tx = TransactionBuilder::default()
+            .add_operation(test::operation(
+                deposit.to_owned(),
+                usd.to_owned(),
+                dec!(1000.0000000),
+            ))
+            .add_operation(test::operation(
+                deposit.to_owned(),
+                usd.to_owned(),
+                dec!(500.004),
+            ))
+            .add_operation(test::operation(
+                deposit.to_owned(),
+                btc.to_owned(),
+                dec!(0.333333333333),
+            ))
+            .add_operation(test::operation(
+                deposit.to_owned(),
+                btc.to_owned(),
+                dec!(0.333333333333),
+            ))
+            .add_operation(test::operation(
+                deposit,
+                btc.to_owned(),
+                dec!(0.333333333333),
+            ))
+            .build()
+            .unwrap();
+
+        let net_values = tx.net_value_by_asset().unwrap();
+
+        assert_eq!(net_values[&usd].to_string(), "1500");
+        assert_eq!(net_values[&btc].to_string(), "1");
     }
 
     #[quickcheck_macros::quickcheck]
@@ -99,7 +995,7 @@ mod tests {
     fn builder_returns_tx_when_multiple_operations_provided(operations: Vec<Operation>) {
         // sometimes there's no sample provided
         if operations.is_empty() {
-            return ;
+            return;
         }
 
         let mut tx_builder = TransactionBuilder::default();
@@ -112,4 +1008,14 @@ mod tests {
 
         assert_ok!(tx);
     }
+
+    #[quickcheck_macros::quickcheck]
+    fn built_dates_span_the_operations(operations: Vec<Operation>) {
+        let executed_at = operations.iter().map(|op| op.executed_at);
+        let span = executed_at.clone().min().zip(executed_at.max());
+
+        let tx = Transaction::from_operations(operations);
+
+        assert_eq!(tx.map(|tx| (tx.started_at, tx.finished_at)).ok(), span);
+    }
 }
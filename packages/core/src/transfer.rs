@@ -0,0 +1,222 @@
+//! Finds moves between the user's own ledgers, which show up as an outflow
+//! from one ledger and an inflow into another, so that they aren't taken for
+//! income or expenses.
+
+use chrono::Duration;
+
+use crate::{
+    operation::{Operation, OperationId, OperationKind},
+    transaction::Transaction,
+};
+
+/// Tag of transactions made of transfers only, see [`tag_transfers`].
+pub const TRANSFER_TAG: &str = "transfer";
+
+/// Outflow and inflow making a single transfer. Transactions are referred to
+/// by their index in the slice they were detected in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransferPair {
+    pub outflow_tx: usize,
+    pub outflow: OperationId,
+    pub inflow_tx: usize,
+    pub inflow: OperationId,
+}
+
+/// Pairs outflows with inflows of the same value of an asset into another
+/// ledger, executed at most `tolerance` apart.
+///
+/// An operation naming its [`Operation::counterparty`] is only paired with an
+/// operation in that ledger. Every operation makes at most one pair.
+///
+/// Outflows are paired in the order of execution, each with the closest
+/// inflow in time still unpaired, the earlier one (then the one with the
+/// lower id) when two are equally close. So the same transactions always
+/// give the same pairs.
+pub fn detect_transfers(txs: &[Transaction], tolerance: Duration) -> Vec<TransferPair> {
+    let legs = |is_outflow: bool| {
+        let mut legs = txs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, tx)| tx.iter().map(move |op| (i, op)))
+            .filter(|(_, op)| match op.kind {
+                OperationKind::Outflow(_) => is_outflow,
+                OperationKind::Inflow(_) => !is_outflow,
+                OperationKind::Adjustment(_) => false,
+            })
+            .collect::<Vec<_>>();
+        legs.sort_by_key(|&(_, op)| op);
+
+        legs
+    };
+
+    let outflows = legs(true);
+    let mut inflows = legs(false).into_iter().map(Some).collect::<Vec<_>>();
+    let mut pairs = vec![];
+
+    for (outflow_tx, outflow) in outflows {
+        let candidate = inflows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, inflow)| inflow.map(|(_, op)| (i, op)))
+            .filter(|(_, inflow)| is_transfer(outflow, inflow, tolerance))
+            // inflows are ordered, so the first of the closest wins a tie
+            .min_by_key(|(_, inflow)| (inflow.executed_at - outflow.executed_at).abs());
+
+        if let Some((i, _)) = candidate {
+            let (inflow_tx, inflow) = inflows[i].take().unwrap();
+
+            pairs.push(TransferPair {
+                outflow_tx,
+                outflow: outflow.id.to_owned(),
+                inflow_tx,
+                inflow: inflow.id.to_owned(),
+            });
+        }
+    }
+
+    pairs
+}
+
+fn is_transfer(outflow: &Operation, inflow: &Operation, tolerance: Duration) -> bool {
+    let names = |op: &Operation, other: &Operation| {
+        op.counterparty
+            .as_ref()
+            .is_none_or(|counterparty| counterparty == &other.ledger)
+    };
+
+    outflow.ledger != inflow.ledger
+        && outflow.asset.id() == inflow.asset.id()
+        && outflow.value == inflow.value
+        && (inflow.executed_at - outflow.executed_at).abs() <= tolerance
+        && names(outflow, inflow)
+        && names(inflow, outflow)
+}
+
+/// Detects transfers (see [`detect_transfers`]) and tags with
+/// [`TRANSFER_TAG`] the transactions all of whose operations, adjustments
+/// aside, are paired, which leaves them out of tax reports.
+pub fn tag_transfers(txs: &mut [Transaction], tolerance: Duration) -> Vec<TransferPair> {
+    let pairs = detect_transfers(txs, tolerance);
+
+    for (i, tx) in txs.iter_mut().enumerate() {
+        let is_paired = |op: &Operation| {
+            pairs.iter().any(|pair| {
+                (pair.outflow_tx == i && pair.outflow == op.id)
+                    || (pair.inflow_tx == i && pair.inflow == op.id)
+            })
+        };
+
+        let all_paired = tx
+            .iter()
+            .filter(|op| !matches!(op.kind, OperationKind::Adjustment(_)))
+            .all(is_paired);
+
+        if all_paired {
+            tx.tags.insert(TRANSFER_TAG.to_owned());
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::{
+        asset::{AssetId, FiatCurrency},
+        ledger::Ledger,
+        operation::{test::operation, InflowOperation, OutflowOperation},
+        tax::{classify_tax_category, TaxCategory},
+    };
+
+    fn leg(id: &str, kind: OperationKind, ledger: &str, minute: u32) -> Transaction {
+        Transaction::from_operations(vec![Operation {
+            id: id.parse().unwrap(),
+            ledger: Ledger::new(ledger),
+            executed_at: Utc.with_ymd_and_hms(2021, 1, 1, 10, minute, 0).unwrap(),
+            ..operation(kind, AssetId::Currency(FiatCurrency::USD), dec!(500))
+        }])
+        .unwrap()
+    }
+
+    #[test]
+    fn outflow_and_inflow_between_ledgers_are_a_transfer() {
+        let mut txs = vec![
+            leg(
+                "OP1",
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                "Brokerage",
+                0,
+            ),
+            leg(
+                "OP2",
+                OperationKind::Inflow(InflowOperation::Income),
+                "Savings",
+                5,
+            ),
+            // too late to pair
+            leg(
+                "OP3",
+                OperationKind::Inflow(InflowOperation::Income),
+                "Savings",
+                30,
+            ),
+        ];
+
+        let pairs = tag_transfers(&mut txs, Duration::minutes(10));
+
+        assert_eq!(pairs, vec![TransferPair {
+            outflow_tx: 0,
+            outflow: "OP1".parse().unwrap(),
+            inflow_tx: 1,
+            inflow: "OP2".parse().unwrap(),
+        }]);
+        assert!(txs[1].has_tag(TRANSFER_TAG));
+        assert!(!txs[2].has_tag(TRANSFER_TAG));
+        assert_eq!(classify_tax_category(&txs[1]), TaxCategory::NonTaxable);
+        assert_eq!(classify_tax_category(&txs[2]), TaxCategory::OtherIncome);
+    }
+
+    #[test]
+    fn closest_candidate_is_paired() {
+        let txs = [
+            leg(
+                "OP1",
+                OperationKind::Outflow(OutflowOperation::Withdrawal),
+                "Brokerage",
+                10,
+            ),
+            leg(
+                "OP2",
+                OperationKind::Inflow(InflowOperation::Deposit),
+                "Savings",
+                4,
+            ),
+            leg(
+                "OP3",
+                OperationKind::Inflow(InflowOperation::Deposit),
+                "Checking",
+                16,
+            ),
+            leg(
+                "OP4",
+                OperationKind::Inflow(InflowOperation::Deposit),
+                "Savings",
+                12,
+            ),
+        ];
+
+        let pairs = detect_transfers(&txs, Duration::minutes(10));
+        let reversed = txs.iter().rev().cloned().collect::<Vec<_>>();
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].inflow, "OP4".parse().unwrap());
+        assert_eq!(
+            detect_transfers(&reversed, Duration::minutes(10))[0].inflow,
+            pairs[0].inflow
+        );
+    }
+}
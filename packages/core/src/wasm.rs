@@ -0,0 +1,13 @@
+//! Entry points for WebAssembly consumers.
+
+use wasm_bindgen::prelude::*;
+
+use crate::data_sources::exante;
+
+/// Imports an Exante CSV export and returns its transactions as JSON.
+#[wasm_bindgen]
+pub fn import_exante_csv(contents: &str) -> Result<JsValue, JsValue> {
+    exante::import_csv_str_to_json(contents)
+        .map(|json| JsValue::from_str(&json))
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
@@ -0,0 +1,84 @@
+//! Uses the model through `core` and `alloc` only, as a `no_std` dependent
+//! would.
+//!
+//! `cargo test -p delfin --no-default-features --test no_std_model` runs it
+//! against the crate built without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{string::ToString, vec::Vec};
+
+use chrono::{TimeZone, Utc};
+use delfin::{
+    asset::{resolve_security, Asset, AssetId, FiatCurrency, SecurityId, ISIN},
+    ledger::Ledger,
+    operation::{operation_kind_from, InflowOperation, Operation, OperationKind},
+};
+use rust_decimal_macros::dec;
+
+#[test]
+fn isin_is_parsed_and_securities_are_resolved() {
+    let isin = "US-0378331005".parse::<ISIN>().unwrap();
+
+    assert_eq!(isin.as_str(), "US0378331005");
+    assert_eq!(isin.country_code(), "US");
+    assert!("US037833100".parse::<ISIN>().is_err());
+    assert!("US03783310０5".parse::<ISIN>().is_err());
+
+    assert_eq!(
+        resolve_security(" aapl ", None),
+        Some(SecurityId::Ticker {
+            symbol: "AAPL".to_string(),
+            country: None,
+        })
+    );
+    assert_eq!(resolve_security("-AAPL", None), None);
+}
+
+#[test]
+fn ledger_hierarchy_is_walked() {
+    let ledger = "Assets:Brokerage:USD".parse::<Ledger>().unwrap();
+
+    assert_eq!(ledger.parent(), Some(Ledger::new("Assets:Brokerage")));
+    assert!(ledger.is_descendant_of(&Ledger::new("Assets")));
+    assert_eq!(
+        Ledger::canonical(" Assets :  Main  Brokerage "),
+        Ledger::new("Assets:Main Brokerage")
+    );
+}
+
+#[test]
+fn operation_is_built_and_displayed() {
+    let kind = operation_kind_from(dec!(12.5), "DIVIDEND");
+    let operation = Operation::builder()
+        .id("OP1".parse().unwrap())
+        .kind(kind)
+        .ledger(Ledger::new("Brokerage"))
+        .asset(Asset::new(
+            AssetId::Currency(FiatCurrency::USD),
+            "USD".to_string(),
+        ))
+        .value(dec!(12.5))
+        .executed_at(Utc.with_ymd_and_hms(2021, 1, 1, 10, 0, 0).unwrap())
+        .build();
+
+    assert_eq!(
+        operation.kind,
+        OperationKind::Inflow(InflowOperation::Dividend)
+    );
+    assert_eq!(operation.signed_value(), dec!(12.5));
+    assert_eq!(
+        operation.to_string(),
+        "2021-01-01 Dividend +12.50 USD (Ledger: Brokerage)"
+    );
+
+    let earlier = Operation {
+        executed_at: Utc.with_ymd_and_hms(2020, 12, 31, 10, 0, 0).unwrap(),
+        ..operation.clone()
+    };
+    let mut operations = Vec::from([operation, earlier.clone()]);
+    operations.sort();
+    assert_eq!(operations[0], earlier);
+}